@@ -21,7 +21,7 @@ impl Display for RequestArguments {
             RequestArguments::pause(..) => write!(f, "pause"),
             RequestArguments::continue_(..) => write!(f, "continue"),
             RequestArguments::next(..) => write!(f, "next"),
-            RequestArguments::stepInTargets(..) => todo!(),
+            RequestArguments::stepInTargets(..) => write!(f, "stepInTargets"),
             RequestArguments::stepIn(..) => write!(f, "stepIn"),
             RequestArguments::stepOut(..) => write!(f, "stepOut"),
             RequestArguments::stepBack(..) => write!(f, "stepBack"),
@@ -38,7 +38,7 @@ impl Display for RequestArguments {
             RequestArguments::restartFrame(..) => write!(f, "restartFrame"),
             RequestArguments::evaluate(..) => write!(f, "evaluate"),
             RequestArguments::setVariable(..) => write!(f, "setVariable"),
-            RequestArguments::dataBreakpointInfo(..) => todo!(),
+            RequestArguments::dataBreakpointInfo(..) => write!(f, "dataBreakpointInfo"),
             RequestArguments::setDataBreakpoints(..) => write!(f, "setDataBreakpoints"),
             RequestArguments::disassemble(..) => write!(f, "disassemble"),
             RequestArguments::readMemory(..) => write!(f, "readMemory"),
@@ -82,15 +82,15 @@ impl Display for ResponseBody {
             ResponseBody::initialize(..) => write!(f, "initialize"),
             ResponseBody::cancel => write!(f, "cancel"),
             ResponseBody::launch(..) => write!(f, "launch"),
-            ResponseBody::attach => write!(f, "attach"),
+            ResponseBody::attach(..) => write!(f, "attach"),
             ResponseBody::restart => write!(f, "restart"),
             ResponseBody::setBreakpoints(..) => write!(f, "setBreakpoints"),
             ResponseBody::setInstructionBreakpoints(..) => write!(f, "setInstructionBreakpoints"),
-            ResponseBody::setFunctionBreakpoints(..) => todo!(),
-            ResponseBody::setExceptionBreakpoints => write!(f, "setExceptionBreakpoints"),
+            ResponseBody::setFunctionBreakpoints(..) => write!(f, "setFunctionBreakpoints"),
+            ResponseBody::setExceptionBreakpoints(..) => write!(f, "setExceptionBreakpoints"),
             ResponseBody::exceptionInfo(..) => write!(f, "exceptionInfo"),
             ResponseBody::configurationDone(..) => todo!(),
-            ResponseBody::pause => write!(f, "pause"),
+            ResponseBody::pause(..) => write!(f, "pause"),
             ResponseBody::continue_(..) => write!(f, "continue_"),
             ResponseBody::next(..) => todo!(),
             ResponseBody::stepInTargets(..) => write!(f, "stepInTargets"),
@@ -110,7 +110,7 @@ impl Display for ResponseBody {
             ResponseBody::restartFrame => write!(f, "restartFrame"),
             ResponseBody::evaluate(..) => write!(f, "evaluate"),
             ResponseBody::setVariable(..) => write!(f, "setVariable"),
-            ResponseBody::dataBreakpointInfo(..) => todo!(),
+            ResponseBody::dataBreakpointInfo(..) => write!(f, "dataBreakpointInfo"),
             ResponseBody::setDataBreakpoints(..) => write!(f, "setDataBreakpoints"),
             ResponseBody::disassemble(..) => todo!(),
             ResponseBody::readMemory(..) => write!(f, "readMemory"),
@@ -32,22 +32,23 @@ pub use crate::dap_types::{
     DataBreakpointInfoArguments, DataBreakpointInfoResponseBody, DisassembleArguments,
     DisassembleResponseBody, DisassembledInstruction, DisconnectArguments, EvaluateArguments,
     EvaluateResponseBody, ExceptionBreakMode, ExceptionBreakpointsFilter, ExceptionInfoArguments,
-    ExceptionInfoResponseBody, ExitedEventBody, GotoArguments, GotoTarget, GotoTargetsArguments,
-    GotoTargetsResponseBody, InitializeRequestArguments, InstructionBreakpoint, InvalidatedAreas,
-    InvalidatedEventBody, Module, ModuleEventBody, ModulesArguments, ModulesResponseBody,
-    NextArguments, OutputEventBody, PauseArguments, ProcessEventBody, ReadMemoryArguments,
-    ReadMemoryResponseBody, RestartFrameArguments, ReverseContinueArguments,
-    RunInTerminalRequestArguments, RunInTerminalResponseBody, Scope, ScopesArguments,
-    ScopesResponseBody, SetBreakpointsArguments, SetBreakpointsResponseBody,
+    ExceptionInfoResponseBody, ExitedEventBody, FunctionBreakpoint, GotoArguments, GotoTarget,
+    GotoTargetsArguments, GotoTargetsResponseBody, InitializeRequestArguments,
+    InstructionBreakpoint, InvalidatedAreas, InvalidatedEventBody, Module, ModuleEventBody,
+    ModulesArguments, ModulesResponseBody, NextArguments, OutputEventBody, PauseArguments,
+    ProcessEventBody, ReadMemoryArguments, ReadMemoryResponseBody, RestartFrameArguments,
+    ReverseContinueArguments, RunInTerminalRequestArguments, RunInTerminalResponseBody, Scope,
+    ScopesArguments, ScopesResponseBody, SetBreakpointsArguments, SetBreakpointsResponseBody,
     SetDataBreakpointsArguments, SetDataBreakpointsResponseBody, SetExceptionBreakpointsArguments,
-    SetFunctionBreakpointsArguments, SetInstructionBreakpointsArguments,
-    SetInstructionBreakpointsResponseBody, SetVariableArguments, SetVariableResponseBody, Source,
-    SourceArguments, SourceBreakpoint, SourceResponseBody, StackFrame, StackTraceArguments,
-    StackTraceResponseBody, StepBackArguments, StepInArguments, StepInTarget,
-    StepInTargetsArguments, StepInTargetsResponseBody, StepOutArguments, SteppingGranularity,
-    StoppedEventBody, TerminateArguments, TerminatedEventBody, Thread, ThreadEventBody,
-    ThreadsResponseBody, Variable, VariablePresentationHint, VariablesArguments,
-    VariablesResponseBody, WriteMemoryArguments, WriteMemoryResponseBody,
+    SetExceptionBreakpointsResponseBody, SetFunctionBreakpointsArguments,
+    SetInstructionBreakpointsArguments, SetInstructionBreakpointsResponseBody,
+    SetVariableArguments, SetVariableResponseBody, Source, SourceArguments, SourceBreakpoint,
+    SourceResponseBody, StackFrame, StackTraceArguments, StackTraceResponseBody, StepBackArguments,
+    StepInArguments, StepInTarget, StepInTargetsArguments, StepInTargetsResponseBody,
+    StepOutArguments, SteppingGranularity, StoppedEventBody, TerminateArguments,
+    TerminatedEventBody, Thread, ThreadEventBody, ThreadsResponseBody, Variable,
+    VariablePresentationHint, VariablesArguments, VariablesResponseBody, WriteMemoryArguments,
+    WriteMemoryResponseBody,
 };
 
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -156,15 +157,15 @@ pub enum ResponseBody {
     initialize(Capabilities),
     cancel,
     launch(Option<NoArguments>),
-    attach,
+    attach(Option<NoArguments>),
     restart,
     setBreakpoints(SetBreakpointsResponseBody),
     setInstructionBreakpoints(SetInstructionBreakpointsResponseBody),
     setFunctionBreakpoints(SetBreakpointsResponseBody),
-    setExceptionBreakpoints,
+    setExceptionBreakpoints(Option<SetExceptionBreakpointsResponseBody>),
     exceptionInfo(ExceptionInfoResponseBody),
     configurationDone(Option<NoArguments>),
-    pause,
+    pause(Option<NoArguments>),
     #[serde(rename = "continue")]
     continue_(ContinueResponseBody),
     next(Option<NoArguments>),
@@ -591,6 +592,154 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attach() {
+        let request = parse(
+            br#"{"type":"request", "seq":2, "command":"attach", "arguments":{"processId":4242}}"#,
+        );
+        assert_matches!(
+            request,
+            ProtocolMessage {
+                seq: 2,
+                type_: ProtocolMessageType::Request(RequestArguments::attach(..))
+            }
+        );
+
+        for response in [
+            &br#"{"seq":3,"request_seq":2,"command":"attach","type":"response","success":true}"#[..],
+            br#"{"seq":3,"request_seq":2,"command":"attach","body":null,"type":"response","success":true}"#,
+            br#"{"seq":3,"request_seq":2,"command":"attach","body":{},"type":"response","success":true}"#,
+        ] {
+            assert_matches!(
+                parse(response),
+                ProtocolMessage {
+                    seq: 3,
+                    type_: ProtocolMessageType::Response(Response {
+                        result: ResponseResult::Success {
+                            body: ResponseBody::attach(..),
+                        },
+                        ..
+                    })
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_exception_breakpoints() {
+        for response in [
+            &br#"{"seq":4,"request_seq":3,"command":"setExceptionBreakpoints","type":"response","success":true}"#[..],
+            br#"{"seq":4,"request_seq":3,"command":"setExceptionBreakpoints","body":{"breakpoints":[{"verified":true}]},"type":"response","success":true}"#,
+        ] {
+            assert_matches!(
+                parse(response),
+                ProtocolMessage {
+                    seq: 4,
+                    type_: ProtocolMessageType::Response(Response {
+                        result: ResponseResult::Success {
+                            body: ResponseBody::setExceptionBreakpoints(..),
+                        },
+                        ..
+                    })
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_function_breakpoints() {
+        let response = parse(
+            br#"{"seq":5,"request_seq":4,"command":"setFunctionBreakpoints","body":{"breakpoints":[{"id":1,"verified":true,"line":12}]},"type":"response","success":true}"#,
+        );
+
+        let ProtocolMessage {
+            type_:
+                ProtocolMessageType::Response(Response {
+                    result: ResponseResult::Success { body },
+                    ..
+                }),
+            ..
+        } = response
+        else {
+            panic!("expected successful response, got {response:?}");
+        };
+
+        assert!(
+            matches!(&body, ResponseBody::setFunctionBreakpoints(body) if body.breakpoints.len() == 1)
+        );
+        assert_eq!(body.to_string(), "setFunctionBreakpoints");
+    }
+
+    #[test]
+    fn test_data_breakpoint_info() {
+        let request = parse(
+            br#"{"seq":6,"type":"request","command":"dataBreakpointInfo","arguments":{"variablesReference":1000,"name":"count"}}"#,
+        );
+        let ProtocolMessage {
+            type_: ProtocolMessageType::Request(request),
+            ..
+        } = request
+        else {
+            panic!("expected request, got {request:?}");
+        };
+        assert_eq!(request.to_string(), "dataBreakpointInfo");
+
+        let response = parse(
+            br#"{"seq":7,"request_seq":6,"command":"dataBreakpointInfo","body":{"dataId":"0x7ffe1234,4","description":"count","accessTypes":["write"]},"type":"response","success":true}"#,
+        );
+        let ProtocolMessage {
+            type_:
+                ProtocolMessageType::Response(Response {
+                    result: ResponseResult::Success { body },
+                    ..
+                }),
+            ..
+        } = response
+        else {
+            panic!("expected successful response, got {response:?}");
+        };
+
+        assert!(
+            matches!(&body, ResponseBody::dataBreakpointInfo(body) if body.data_id.as_deref() == Some("0x7ffe1234,4"))
+        );
+        assert_eq!(body.to_string(), "dataBreakpointInfo");
+    }
+
+    #[test]
+    fn test_step_in_targets() {
+        let request = parse(
+            br#"{"seq":8,"type":"request","command":"stepInTargets","arguments":{"frameId":1000}}"#,
+        );
+        let ProtocolMessage {
+            type_: ProtocolMessageType::Request(request),
+            ..
+        } = request
+        else {
+            panic!("expected request, got {request:?}");
+        };
+        assert_eq!(request.to_string(), "stepInTargets");
+
+        let response = parse(
+            br#"{"seq":9,"request_seq":8,"command":"stepInTargets","body":{"targets":[{"id":1,"label":"Scale"},{"id":2,"label":"Apply","line":12}]},"type":"response","success":true}"#,
+        );
+        let ProtocolMessage {
+            type_:
+                ProtocolMessageType::Response(Response {
+                    result: ResponseResult::Success { body },
+                    ..
+                }),
+            ..
+        } = response
+        else {
+            panic!("expected successful response, got {response:?}");
+        };
+
+        assert!(
+            matches!(&body, ResponseBody::stepInTargets(body) if body.targets[1].line == Some(12))
+        );
+        assert_eq!(body.to_string(), "stepInTargets");
+    }
+
     #[test]
     fn test_unknown() {
         let request = parse(br#"{"type":"request", "seq":12, "command":"foobar"}"#);
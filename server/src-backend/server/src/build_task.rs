@@ -0,0 +1,350 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::Context as _;
+use itertools::Itertools as _;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt as _, BufReader},
+    process::Command,
+};
+
+use crate::launch_json::{strip_jsonc, substitute_variables};
+
+/// A command building the program before it gets launched, so we never debug a stale build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildTask {
+    pub command: String,
+    pub args: Vec<String>,
+    /// whether `command` and `args` form a command line for the system shell instead of naming a
+    /// program to run
+    pub shell: bool,
+    /// runs in the current directory if not set
+    pub cwd: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct TasksJson {
+    #[serde(default)]
+    tasks: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskDefinition {
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<TaskArgument>,
+    #[serde(default)]
+    options: TaskOptions,
+    depends_on: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskArgument {
+    Plain(String),
+    Quoted { value: String },
+}
+
+#[derive(Deserialize, Default)]
+struct TaskOptions {
+    cwd: Option<PathBuf>,
+}
+
+impl BuildTask {
+    /// A command line run by the system shell, as passed to `--build-cmd`.
+    pub fn shell(command_line: &str) -> Self {
+        BuildTask {
+            command: command_line.into(),
+            args: Vec::new(),
+            shell: true,
+            cwd: None,
+        }
+    }
+
+    /// Loads the task labeled `label` from the `.vscode/tasks.json` of `workspace_folder`.
+    pub fn find(workspace_folder: &Path, label: &str) -> anyhow::Result<Self> {
+        let path = workspace_folder.join(".vscode").join("tasks.json");
+        let tasks_json = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        BuildTask::parse(&tasks_json, workspace_folder, label)
+            .with_context(|| format!("invalid task in {}", path.display()))
+    }
+
+    pub fn parse(tasks_json: &str, workspace_folder: &Path, label: &str) -> anyhow::Result<Self> {
+        let tasks_json: TasksJson = serde_json::from_str(&strip_jsonc(tasks_json))?;
+
+        let labels = || {
+            tasks_json
+                .tasks
+                .iter()
+                .filter_map(|task| task["label"].as_str())
+                .join(", ")
+        };
+
+        let mut task = tasks_json
+            .tasks
+            .iter()
+            .find(|task| task["label"] == label)
+            .cloned()
+            .with_context(|| format!("no task labeled {label:?}, found: {}", labels()))?;
+
+        substitute_variables(&mut task, workspace_folder);
+
+        let task: TaskDefinition = serde_json::from_value(task)?;
+
+        if task.depends_on.is_some() {
+            tracing::warn!("Ignoring dependencies of task {label:?}, only the task itself is run");
+        }
+
+        Ok(BuildTask {
+            command: task
+                .command
+                .with_context(|| format!("task {label:?} has no command"))?,
+            args: task
+                .args
+                .into_iter()
+                .map(|argument| match argument {
+                    TaskArgument::Plain(value) | TaskArgument::Quoted { value } => value,
+                })
+                .collect(),
+            shell: task.type_.as_deref() != Some("process"),
+            // vscode runs tasks in the workspace folder by default
+            cwd: Some(
+                task.options
+                    .cwd
+                    .unwrap_or_else(|| workspace_folder.to_owned()),
+            ),
+        })
+    }
+
+    /// Runs the task to completion, logging every line it prints and handing it to `on_output`.
+    /// Fails if the task does not succeed.
+    pub async fn run(&self, mut on_output: impl FnMut(String)) -> anyhow::Result<()> {
+        let mut command = match self.shell {
+            true if cfg!(windows) => {
+                let mut command = Command::new("cmd");
+                command.arg("/C").arg(self.command_line());
+                command
+            }
+            true => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(self.command_line());
+                command
+            }
+            false => {
+                let mut command = Command::new(&self.command);
+                command.args(&self.args);
+                command
+            }
+        };
+
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("could not start build command `{self}`"))?;
+
+        let stdout = child.stdout.take().context("build has no stdout handle")?;
+        let stderr = child.stderr.take().context("build has no stderr handle")?;
+        let mut stdout = BufReader::new(stdout).split(b'\n');
+        let mut stderr = BufReader::new(stderr).split(b'\n');
+        let (mut stdout_open, mut stderr_open) = (true, true);
+
+        while stdout_open || stderr_open {
+            let line = tokio::select! {
+                line = stdout.next_segment(), if stdout_open => line?.or_else(|| {
+                    stdout_open = false;
+                    None
+                }),
+                line = stderr.next_segment(), if stderr_open => line?.or_else(|| {
+                    stderr_open = false;
+                    None
+                }),
+            };
+
+            if let Some(line) = line {
+                // build tools on windows don't necessarily print utf-8
+                let line = String::from_utf8_lossy(&line).trim_end().to_owned();
+
+                tracing::info!("[build] {line}");
+                on_output(line);
+            }
+        }
+
+        let status = child.wait().await?;
+        anyhow::ensure!(
+            status.success(),
+            "build command `{self}` failed ({status}), not launching a stale program"
+        );
+
+        Ok(())
+    }
+}
+
+impl BuildTask {
+    /// The command line for the system shell. Like vscode does, `command` is taken as it is while
+    /// every argument gets quoted, so substituted paths containing spaces or shell
+    /// metacharacters reach the program unchanged.
+    fn command_line(&self) -> String {
+        std::iter::once(Cow::from(&self.command))
+            .chain(
+                self.args
+                    .iter()
+                    .map(|argument| quote(argument, cfg!(windows))),
+            )
+            .join(" ")
+    }
+}
+
+/// Quotes `argument` for `sh`, or for `cmd` if `windows`. `cmd` still expands `%variables%`
+/// within quotes, there is no way around that.
+fn quote(argument: &str, windows: bool) -> Cow<'_, str> {
+    let plain = !argument.is_empty()
+        && argument
+            .chars()
+            // backslashes only separate paths on windows
+            .all(|c| {
+                c.is_ascii_alphanumeric() || "-_./=:,@+".contains(c) || (windows && c == '\\')
+            });
+
+    match (plain, windows) {
+        (true, _) => argument.into(),
+        (false, true) => format!("\"{}\"", argument.replace('"', "\\\"")).into(),
+        (false, false) => format!("'{}'", argument.replace('\'', "'\\''")).into(),
+    }
+}
+
+impl Display for BuildTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.command_line())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{BuildTask, quote};
+
+    const TASKS_JSON: &str = r#"{
+        "version": "2.0.0",
+        "tasks": [
+            {
+                "label": "build",
+                "command": "dotnet",
+                "type": "process",
+                "args": [
+                    "build",
+                    "${workspaceFolder}/Exercise.csproj",
+                    // emitted by the vscode template
+                    { "value": "/property:GenerateFullPaths=true", "quoting": "escape" },
+                ],
+                "problemMatcher": "$msCompile",
+            },
+            {
+                "label": "test",
+                "command": "dotnet test",
+                "type": "shell",
+                "options": { "cwd": "${workspaceFolder}/tests" },
+            },
+        ],
+    }"#;
+
+    #[test]
+    fn parses_task_by_label() {
+        let workspace_folder = Path::new("/home/student/exercise");
+
+        assert_eq!(
+            BuildTask::parse(TASKS_JSON, workspace_folder, "build").unwrap(),
+            BuildTask {
+                command: "dotnet".into(),
+                args: vec![
+                    "build".into(),
+                    "/home/student/exercise/Exercise.csproj".into(),
+                    "/property:GenerateFullPaths=true".into(),
+                ],
+                shell: false,
+                cwd: Some(workspace_folder.into()),
+            }
+        );
+
+        let test = BuildTask::parse(TASKS_JSON, workspace_folder, "test").unwrap();
+        assert!(test.shell);
+        assert_eq!(
+            test.cwd,
+            Some(PathBuf::from("/home/student/exercise/tests"))
+        );
+
+        let err = BuildTask::parse(TASKS_JSON, workspace_folder, "publish").unwrap_err();
+        assert!(err.to_string().contains("found: build, test"));
+    }
+
+    #[tokio::test]
+    async fn streams_output_of_build() {
+        let mut output = Vec::new();
+
+        BuildTask::shell("echo compiling && echo warning 1>&2")
+            .run(|line| output.push(line))
+            .await
+            .unwrap();
+
+        output.sort();
+        assert_eq!(output, ["compiling", "warning"]);
+    }
+
+    #[tokio::test]
+    async fn failed_build_is_an_error() {
+        let err = BuildTask::shell("exit 3").run(|_| ()).await.unwrap_err();
+
+        assert!(err.to_string().contains("`exit 3` failed"));
+    }
+
+    #[test]
+    fn quotes_arguments_for_the_shell() {
+        assert_eq!(
+            quote("/property:GenerateFullPaths=true", false),
+            "/property:GenerateFullPaths=true"
+        );
+        assert_eq!(
+            quote("C:\\Users\\Jane Doe", true),
+            "\"C:\\Users\\Jane Doe\""
+        );
+        assert_eq!(quote("say \"hi\"", true), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote("it's; $HOME", false), "'it'\\''s; $HOME'");
+        assert_eq!(quote("", false), "''");
+        assert_eq!(quote("C:\\Users", false), "'C:\\Users'");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn passes_arguments_with_spaces_to_shell_tasks() {
+        let mut output = Vec::new();
+        let path = "/home/student/my exercise; $HOME/Exercise.csproj";
+
+        BuildTask {
+            args: vec![path.into()],
+            ..BuildTask::shell("echo")
+        }
+        .run(|line| output.push(line))
+        .await
+        .unwrap();
+
+        assert_eq!(output, [path]);
+    }
+}
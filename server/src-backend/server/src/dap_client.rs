@@ -1,72 +1,138 @@
-use std::{ops::DerefMut, process::Stdio, sync::atomic::AtomicUsize};
+use std::{
+    collections::HashMap,
+    ops::DerefMut,
+    path::PathBuf,
+    sync::{Mutex, atomic::AtomicUsize},
+    time::Duration,
+};
 
 use anyhow::Context;
-use bytes::Bytes;
-use dap_types::types::{ProtocolMessage, RequestArguments};
+use bytes::BytesMut;
+use dap_types::types::{
+    CancelArguments, EventBody, ProtocolMessage, ProtocolMessageType, RequestArguments, Response,
+    ResponseBody, ResponseResult,
+};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
-    process::{ChildStdin, ChildStdout},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time::Instant,
 };
 
 use crate::{
-    dap_states::{dap_state_machine::DapStateMachine, visualization_state::VisualizationState},
+    build_task::BuildTask,
+    dap_codec::DapCodec,
+    dap_recorder::{DapRecorder, DapTraceDirection},
+    dap_states::{
+        dap_state_machine::{DapStateMachine, DebugTarget},
+        visualization_state::VisualizationState,
+    },
+    dap_transport::{DapReader, DapTransport, DapWriter},
     debug_adapters::DebugAdapter,
-    user_request::UserRequest,
+    user_request::{FunctionBreakpoint, UserRequest},
 };
 
 pub struct DapLaunchInfo {
-    pub executable_path: String,
-    pub debugger_path: String,
+    pub target: DebugTarget,
+    /// runs before the debug adapter is started
+    pub build_task: Option<BuildTask>,
+    /// set before the program starts
+    pub function_breakpoints: Vec<FunctionBreakpoint>,
+    pub transport: DapTransport,
     pub debug_adapter: DebugAdapter,
+    pub request_timeout: Duration,
+    pub record_dap: Option<PathBuf>,
 }
 
 pub struct DapClient {
     visualization_state_sender: tokio::sync::watch::Sender<VisualizationState>,
     user_request_receiver: tokio::sync::broadcast::Receiver<UserRequest>,
+    /// once `true`, the debuggee gets ended and the dap client returns
+    shutdown: tokio::sync::watch::Receiver<bool>,
 }
 
-struct DapProcess {
-    _process: tokio::process::Child,
-    stdin: tokio::sync::Mutex<ChildStdin>,
-    stdout: tokio::sync::Mutex<BufReader<ChildStdout>>,
+/// A message of the debug adapter, with responses already matched to the request they answer.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum DapMessage {
+    Response {
+        request: RequestArguments,
+        response: Response,
+    },
+    Event(EventBody),
+    ReverseRequest(RequestArguments),
+}
+
+/// Connection to a debug adapter, usually a spawned child process.
+pub struct DapProcess {
+    // only held so the debug adapter gets killed when we are dropped
+    _process: Option<tokio::process::Child>,
+    writer: tokio::sync::Mutex<DapWriter>,
+    // bytes read that have not been decoded into a message yet. kept next to the stream so a
+    // cancelled receive never loses a partially read message
+    reader: tokio::sync::Mutex<(DapReader, BytesMut)>,
+    codec: DapCodec,
     sequence_id: AtomicUsize,
+    // requests that were sent but not answered yet, keyed by their seq
+    pending_requests: Mutex<HashMap<usize, PendingRequest>>,
+    recorder: Option<DapRecorder>,
+}
+
+struct PendingRequest {
+    arguments: RequestArguments,
+    sent_at: Instant,
 }
 
 impl DapProcess {
     async fn start(launch_info: &DapLaunchInfo) -> anyhow::Result<Self> {
-        let mut command = match launch_info.debug_adapter {
-            DebugAdapter::NetCoreDbg => {
-                let mut command = tokio::process::Command::new(&launch_info.debugger_path);
-                command.arg("--interpreter=vscode");
-                command
-            }
-        };
+        let connection = launch_info
+            .transport
+            .connect(&launch_info.debug_adapter)
+            .await?;
+
+        let recorder = launch_info
+            .record_dap
+            .as_deref()
+            .map(DapRecorder::create)
+            .transpose()?;
+
+        Ok(DapProcess::new(
+            connection.reader,
+            connection.writer,
+            connection.process,
+            recorder,
+        ))
+    }
+
+    /// Talks to a debug adapter that is not a child process, e.g. one running in this process.
+    pub fn from_io(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        DapProcess::new(Box::new(reader), Box::new(writer), None, None)
+    }
+
+    /// Writes every message exchanged from now on to `recorder`.
+    pub fn record_to(self, recorder: DapRecorder) -> Self {
+        DapProcess {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
 
-        command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            // forward error logs of debug adapter
-            .stderr(Stdio::inherit())
-            .kill_on_drop(true);
-
-        let mut child = command.spawn()?;
-
-        let stdin = child
-            .stdin
-            .take()
-            .context("child process did not have a stdin handle")?;
-
-        let stdout = child
-            .stdout
-            .take()
-            .context("child process did not have a stdout handle")?;
-
-        Ok(DapProcess {
-            _process: child,
-            stdin: stdin.into(),
-            stdout: BufReader::new(stdout).into(),
+    fn new(
+        reader: DapReader,
+        writer: DapWriter,
+        process: Option<tokio::process::Child>,
+        recorder: Option<DapRecorder>,
+    ) -> Self {
+        DapProcess {
+            _process: process,
+            writer: writer.into(),
+            reader: (reader, BytesMut::new()).into(),
+            codec: DapCodec,
             sequence_id: 1.into(),
-        })
+            pending_requests: Mutex::default(),
+            recorder,
+        }
     }
 
     async fn send(&self, requests: &[RequestArguments]) -> anyhow::Result<()> {
@@ -74,74 +140,161 @@ impl DapProcess {
             .sequence_id
             .fetch_add(requests.len(), std::sync::atomic::Ordering::SeqCst);
 
-        let messages = requests
-            .iter()
-            .cloned()
-            .enumerate()
-            .flat_map(move |(i, request)| {
+        let mut bytes = BytesMut::new();
+
+        {
+            let mut pending_requests = self
+                .pending_requests
+                .lock()
+                .expect("pending requests lock should not be poisoned");
+
+            let sent_at = Instant::now();
+
+            for (i, request) in requests.iter().cloned().enumerate() {
+                let seq = first_request_id + i;
+
+                // nobody waits for cancel to be answered, a hanging adapter won't answer it
+                // anyway
+                if !matches!(request, RequestArguments::cancel(..)) {
+                    pending_requests.insert(
+                        seq,
+                        PendingRequest {
+                            arguments: request.clone(),
+                            sent_at,
+                        },
+                    );
+                }
+
                 let message = ProtocolMessage {
-                    seq: first_request_id + i,
-                    type_: dap_types::types::ProtocolMessageType::Request(request),
+                    seq,
+                    type_: ProtocolMessageType::Request(request),
                 };
+                let payload = serde_json::to_vec(&message)
+                    .expect("dap message should not contain map with non-string key");
 
-                let payload = serde_json::to_string(&message)
-                    .expect("dap request should not contain map with non-string key");
-                let length = payload.len();
-
-                format!("Content-Length: {}\r\n\r\n{}", length, payload).into_bytes()
-            });
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(DapTraceDirection::Sent, &payload);
+                }
 
-        let bytes = Bytes::from_iter(messages);
+                self.codec.encode_frame(&payload, &mut bytes);
+            }
+        }
 
-        let mut stdin = self.stdin.lock().await;
-        stdin.write_all(&bytes).await?;
-        stdin.flush().await?;
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&bytes).await?;
+        writer.flush().await?;
 
         Ok(())
     }
 
-    async fn receive(&self) -> anyhow::Result<Vec<ProtocolMessage>> {
-        let mut messages = Vec::new();
+    /// Waits for the next message of the debug adapter. Cancel safe.
+    async fn receive(&self) -> anyhow::Result<DapMessage> {
+        loop {
+            let message = self.receive_protocol_message().await?;
 
-        let mut stdout = self.stdout.lock().await;
-        let reader = stdout.deref_mut();
+            match message.type_ {
+                ProtocolMessageType::Request(request) => {
+                    return Ok(DapMessage::ReverseRequest(request));
+                }
+                ProtocolMessageType::Event(event) => return Ok(DapMessage::Event(event)),
+                ProtocolMessageType::Response(response) => {
+                    let request = self
+                        .pending_requests
+                        .lock()
+                        .expect("pending requests lock should not be poisoned")
+                        .remove(&response.request_seq);
 
-        loop {
-            let mut line_buffer = String::new();
+                    match request {
+                        Some(request) => {
+                            return Ok(DapMessage::Response {
+                                request: request.arguments,
+                                response,
+                            });
+                        }
+                        // we don't care whether the adapter managed to cancel, the request was
+                        // already abandoned when cancel was sent
+                        None if is_cancel_response(&response) => (),
+                        None => tracing::warn!(
+                            "Dropping response to unknown request {}: {:?}",
+                            response.request_seq,
+                            response
+                        ),
+                    }
+                }
+            }
+        }
+    }
 
-            let content_length = {
-                let bytes_read = reader.read_line(&mut line_buffer).await?;
-                anyhow::ensure!(bytes_read != 0, "stdout stream closed");
+    fn has_pending_requests(&self) -> bool {
+        !self
+            .pending_requests
+            .lock()
+            .expect("pending requests lock should not be poisoned")
+            .is_empty()
+    }
 
-                line_buffer.truncate(line_buffer.len() - 2); // remove \r\n
+    /// The point in time at which the oldest unanswered request times out.
+    fn next_timeout(&self, timeout: Duration) -> Option<Instant> {
+        self.pending_requests
+            .lock()
+            .expect("pending requests lock should not be poisoned")
+            .values()
+            .map(|request| request.sent_at + timeout)
+            .min()
+    }
 
-                line_buffer
-                    .split_once("Content-Length: ")
-                    .and_then(|(_, length)| length.parse::<usize>().ok())
-                    .context("could not parse content length header")?
-            };
+    /// Stops waiting for every request older than `timeout`. A late response to one of them will
+    /// be dropped.
+    fn take_timed_out_requests(&self, timeout: Duration) -> Vec<(usize, RequestArguments)> {
+        let now = Instant::now();
 
-            line_buffer.clear();
-            let bytes_read = reader.read_line(&mut line_buffer).await?;
-            anyhow::ensure!(bytes_read != 0, "stdout stream closed");
+        let mut timed_out: Vec<_> = self
+            .pending_requests
+            .lock()
+            .expect("pending requests lock should not be poisoned")
+            .extract_if(|_, request| request.sent_at + timeout <= now)
+            .map(|(seq, request)| (seq, request.arguments))
+            .collect();
 
-            // NOTE: a dap message can have multiple headers, but only Content-Length is required
-            // and standardised. for simplicity only check for this header for now.
-            anyhow::ensure!(line_buffer == "\r\n", "expected end of headers");
+        timed_out.sort_by_key(|(seq, _)| *seq);
+        timed_out
+    }
 
-            let mut message = vec![0u8; content_length];
-            reader.read_exact(&mut message).await?;
+    async fn receive_protocol_message(&self) -> anyhow::Result<ProtocolMessage> {
+        let mut reader = self.reader.lock().await;
+        let (reader, buffer) = reader.deref_mut();
 
-            let parsed = serde_json::from_slice::<ProtocolMessage>(&message)?;
+        loop {
+            let buffered = buffer.len();
 
-            messages.push(parsed);
+            match self.codec.decode_frame(buffer) {
+                Ok(Some(frame)) => {
+                    // recorded as received, including what we can't parse or don't know about
+                    if let Some(recorder) = &self.recorder {
+                        recorder.record(DapTraceDirection::Received, &frame);
+                    }
 
-            if reader.buffer().is_empty() {
-                break;
+                    match serde_json::from_slice::<ProtocolMessage>(&frame) {
+                        Ok(message) => return Ok(message),
+                        // adapters are free to send events and responses we don't know about, don't
+                        // let them take down the whole session
+                        Err(err) => tracing::warn!(
+                            "Skipping unparseable dap message ({err}): {}",
+                            String::from_utf8_lossy(&frame)
+                        ),
+                    }
+                }
+                Ok(None) => {
+                    let bytes_read = reader.read_buf(buffer).await?;
+                    anyhow::ensure!(bytes_read != 0, "debug adapter closed the connection");
+                }
+                // decoding again would fail the same way if nothing was skipped
+                Err(err) if buffer.len() == buffered => {
+                    return Err(err.context("could not decode dap message"));
+                }
+                Err(err) => tracing::error!("Skipping malformed dap message: {err:#}"),
             }
         }
-
-        Ok(messages)
     }
 }
 
@@ -153,48 +306,1285 @@ impl DapClient {
         DapClient {
             visualization_state_sender,
             user_request_receiver,
+            // never shuts down, the sender is gone right away
+            shutdown: tokio::sync::watch::channel(false).1,
         }
     }
 
-    pub async fn run(mut self, launch_info: DapLaunchInfo) -> anyhow::Result<()> {
-        let process = DapProcess::start(&launch_info).await?;
+    /// Ends the debug session as soon as `true` is sent, instead of leaving the debuggee to the
+    /// debug adapter getting killed.
+    pub fn with_shutdown(self, shutdown: tokio::sync::watch::Receiver<bool>) -> Self {
+        DapClient { shutdown, ..self }
+    }
+
+    pub async fn run(self, launch_info: DapLaunchInfo) -> anyhow::Result<()> {
+        if let Some(build_task) = &launch_info.build_task {
+            tracing::info!("Building program with `{build_task}`");
+
+            let build = build_task.run(|line| {
+                self.visualization_state_sender
+                    .send_modify(|state| state.push_build_output(line))
+            });
+
+            // there is no debuggee to end yet, the build gets killed along with its future
+            tokio::select! {
+                result = build => result?,
+                _ = shutdown_requested(&self.shutdown) => return Ok(()),
+            }
+        }
 
-        let mut state_machine =
-            DapStateMachine::new(launch_info.debug_adapter, launch_info.executable_path);
+        let state_machine = DapStateMachine::new(
+            launch_info.debug_adapter.clone(),
+            launch_info.target.clone(),
+        )
+        .with_function_breakpoints(launch_info.function_breakpoints.clone());
+
+        self.run_with(
+            async || DapProcess::start(&launch_info).await,
+            state_machine,
+            launch_info.request_timeout,
+        )
+        .await
+    }
+
+    /// Debugs until the debuggee terminates, connecting to a new debug adapter with `connect`
+    /// whenever the user restarts the session. Connected clients stay connected throughout.
+    pub async fn run_with(
+        mut self,
+        connect: impl AsyncFn() -> anyhow::Result<DapProcess>,
+        mut state_machine: DapStateMachine,
+        request_timeout: Duration,
+    ) -> anyhow::Result<()> {
+        loop {
+            let process = connect().await?;
+
+            state_machine = self
+                .run_session(&process, state_machine, request_timeout)
+                .await?;
+
+            if !state_machine.restart_requested() || self.visualization_state_sender.is_closed() {
+                break;
+            }
+
+            tracing::info!("Restarting debug session");
+            state_machine = state_machine.restarted();
+        }
+
+        self.publish_final_state(&state_machine);
+
+        Ok(())
+    }
+
+    /// Debugs with a debug adapter that is already connected and can't be connected to again,
+    /// e.g. a replay, so the session can't be restarted.
+    pub async fn run_with_process(
+        mut self,
+        process: DapProcess,
+        state_machine: DapStateMachine,
+        request_timeout: Duration,
+    ) -> anyhow::Result<()> {
+        let state_machine = self
+            .run_session(&process, state_machine, request_timeout)
+            .await?;
+
+        if state_machine.restart_requested() {
+            tracing::warn!("This debug adapter can't be started again, not restarting.");
+        }
+
+        self.publish_final_state(&state_machine);
+
+        Ok(())
+    }
+
+    /// Lets connected clients know how the session ended, after which they get disconnected.
+    fn publish_final_state(&self, state_machine: &DapStateMachine) {
+        tracing::info!("Debug session ended");
+
+        // sent even without clients, the webserver might keep serving it to new ones
+        self.visualization_state_sender
+            .send_replace(state_machine.build_visualization_state());
+    }
+
+    /// Runs a single debug session, until the debuggee terminated or the session was ended to
+    /// restart it.
+    async fn run_session(
+        &mut self,
+        process: &DapProcess,
+        mut state_machine: DapStateMachine,
+        request_timeout: Duration,
+    ) -> anyhow::Result<DapStateMachine> {
+        let mut shutting_down = false;
 
         while !state_machine.program_terminated() {
             while let Some(next) = state_machine.next_dap_requests() {
                 process.send(&next).await?;
-                state_machine = state_machine.process_dap_messages(&process.receive().await?);
             }
 
-            let viz = state_machine.build_visualization_state();
-            tracing::debug!(visualization = ?viz, "Sending state to connected clients");
+            // only publish settled states, not ones that are still being queried
+            if !process.has_pending_requests() {
+                let viz = state_machine.build_visualization_state();
+                tracing::debug!(visualization = ?viz, "Sending state to connected clients");
 
-            if self.visualization_state_sender.send(viz).is_err() {
-                break;
+                if self.visualization_state_sender.send(viz).is_err() {
+                    break;
+                }
+            }
+
+            // applied only after the state it follows was published, so a student clicking step
+            // five times sees five steps
+            if state_machine.has_applicable_user_request() {
+                state_machine = state_machine.process_queued_user_request();
+                continue;
             }
 
+            let next_timeout = process.next_timeout(request_timeout);
+
             tokio::select! {
                 request = self.user_request_receiver.recv() => {
                     let request = request.context("no more dap command senders")?;
-
-                    // TODO: add user requests to a queue so inputs don't get discarded if the
-                    // current state can't handle it
                     state_machine = state_machine.process_user_request(&request);
                 },
-                messages = process.receive() => {
-                    let messages = messages.context("could not receive messages")?;
-                    if !messages.is_empty() {
-                        state_machine = state_machine.process_dap_messages(&messages);
-                    }
+                message = process.receive() => {
+                    let message = message.context("could not receive message")?;
+                    state_machine = state_machine.process_dap_message(&message);
+                }
+                _ = tokio::time::sleep_until(next_timeout.unwrap_or_else(Instant::now)),
+                    if next_timeout.is_some() =>
+                {
+                    state_machine =
+                        abandon_timed_out_requests(process, state_machine, request_timeout)
+                            .await?;
+                }
+                // the session ends once the debug adapter answered disconnect, or after the
+                // request timed out. the debug adapter gets killed afterwards, with the process
+                _ = shutdown_requested(&self.shutdown), if !shutting_down => {
+                    tracing::info!("Shutting down, ending the debug session");
+                    shutting_down = true;
+                    state_machine = state_machine.end_session();
                 }
             };
         }
 
-        // TODO: let the websocket listeners know
-        tracing::info!("Debuggee terminated, exiting.");
+        Ok(state_machine)
+    }
+}
 
-        Ok(())
+/// Completes once a shutdown was requested, or never if it can't be anymore.
+async fn shutdown_requested(shutdown: &tokio::sync::watch::Receiver<bool>) {
+    if shutdown
+        .clone()
+        .wait_for(|shutdown| *shutdown)
+        .await
+        .is_err()
+    {
+        std::future::pending().await
+    }
+}
+
+fn is_cancel_response(response: &Response) -> bool {
+    match &response.result {
+        ResponseResult::Success { body } => matches!(body, ResponseBody::cancel),
+        ResponseResult::Error { command, .. } => command == "cancel",
+    }
+}
+
+async fn abandon_timed_out_requests(
+    process: &DapProcess,
+    mut state_machine: DapStateMachine,
+    timeout: Duration,
+) -> anyhow::Result<DapStateMachine> {
+    for (seq, request) in process.take_timed_out_requests(timeout) {
+        if state_machine.supports_cancel_request() {
+            process
+                .send(&[RequestArguments::cancel(CancelArguments {
+                    request_id: Some(seq as i64),
+                    progress_id: None,
+                })])
+                .await?;
+        }
+
+        let message = format!("no response after {} seconds", timeout.as_secs_f32());
+        state_machine = state_machine.process_request_failure(&request, &message);
+    }
+
+    Ok(state_machine)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::{SinkExt as _, StreamExt as _};
+    use serde_json::Value;
+    use tokio::{
+        io::AsyncWriteExt as _,
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message};
+
+    use super::{DapClient, DapProcess};
+    use crate::{
+        dap_recorder::DapRecorder,
+        dap_replay::DapReplay,
+        dap_states::{
+            dap_state_machine::{DapStateMachine, DebugTarget},
+            visualization_state::VisualizationState,
+        },
+        debug_adapters::DebugAdapter,
+        mock_adapter::{
+            MockAdapter, MockFrame, MockScope, MockScript, MockStop, MockThread, MockVariable,
+        },
+        user_request::{FunctionBreakpoint, UserRequest},
+        webserver::Webserver,
+    };
+
+    type EventsSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn stop_at_line(line: i64) -> MockStop {
+        MockStop::new("step").thread(
+            MockThread::new(1, "Main Thread")
+                .frame(
+                    MockFrame::new("Program.Add", "/mock/Program.cs", line).scope(
+                        MockScope::new("Locals")
+                            .variable(MockVariable::new("count", "3", "int"))
+                            .variable(
+                                MockVariable::new("point", "{Point}", "Point")
+                                    .child(MockVariable::new("X", "1", "int"))
+                                    .child(MockVariable::new("Y", "2", "int")),
+                            ),
+                    ),
+                )
+                .frame(MockFrame::new("Program.Main", "/mock/Program.cs", 3)),
+        )
+    }
+
+    /// Runs a dap client against `adapter` and returns a websocket connected to its webserver.
+    async fn start_session(
+        adapter: &MockAdapter,
+        target: DebugTarget,
+    ) -> (EventsSocket, tokio::task::JoinHandle<anyhow::Result<()>>) {
+        start_session_with(
+            adapter,
+            DapStateMachine::new(DebugAdapter::NetCoreDbg, target),
+        )
+        .await
+    }
+
+    async fn start_session_with(
+        adapter: &MockAdapter,
+        state_machine: DapStateMachine,
+    ) -> (EventsSocket, tokio::task::JoinHandle<anyhow::Result<()>>) {
+        let (visualization_state_sender, visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Webserver::new(visualization_state_receiver, user_request_sender)
+                .serve_listener(listener),
+        );
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{address}/api/events"))
+                .await
+                .unwrap();
+        socket.send(Message::text("ready")).await.unwrap();
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver);
+        let adapter = adapter.clone();
+        let session = tokio::spawn(dap_client.run_with(
+            async move || Ok(adapter.start()),
+            state_machine,
+            TIMEOUT,
+        ));
+
+        (socket, session)
+    }
+
+    async fn next_state_matching(
+        socket: &mut EventsSocket,
+        predicate: impl Fn(&Value) -> bool,
+    ) -> Value {
+        let wait_for_state = async {
+            loop {
+                let message = socket
+                    .next()
+                    .await
+                    .expect("websocket closed before the expected state arrived")
+                    .unwrap();
+
+                if let Message::Text(text) = message {
+                    let state: Value = serde_json::from_str(&text).unwrap();
+                    if predicate(&state) {
+                        return state;
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(TIMEOUT, wait_for_state)
+            .await
+            .expect("expected state did not arrive in time")
+    }
+
+    fn launch_target() -> DebugTarget {
+        DebugTarget::launch("/mock/program.dll")
+    }
+
+    fn current_line(state: &Value) -> Option<i64> {
+        state["current_source_file"][2].as_i64()
+    }
+
+    fn thread_request(request_id: u8, thread_id: i64) -> Message {
+        let mut bytes = thread_id.to_le_bytes().to_vec();
+        bytes.push(request_id);
+        Message::binary(bytes)
+    }
+
+    #[tokio::test]
+    async fn visualizes_stopped_program() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        assert_eq!(state["current_source_file"][0], "/mock/Program.cs");
+
+        let thread = &state["threads"][0];
+        assert_eq!(thread["name"], "Main Thread");
+        // outermost frame first
+        assert_eq!(thread["stack_frames"][0]["function"], "Program.Main");
+        assert_eq!(thread["stack_frames"][1]["function"], "Program.Add");
+
+        let locals = thread["stack_frames"][1]["scopes"][0]["variables"]
+            .as_array()
+            .unwrap();
+        assert_eq!(locals.len(), 2);
+
+        let heap_variables = state["heap_variables"].as_array().unwrap();
+        assert_eq!(heap_variables.len(), 1);
+        assert_eq!(heap_variables[0]["name"], "point");
+        assert_eq!(heap_variables[0]["fields"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn steps_until_debuggee_terminates() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(11)),
+        );
+        let (mut socket, session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        socket.send(thread_request(1, 1)).await.unwrap();
+        next_state_matching(&mut socket, |state| current_line(state) == Some(11)).await;
+
+        socket.send(thread_request(2, 1)).await.unwrap();
+        tokio::time::timeout(TIMEOUT, session)
+            .await
+            .expect("session did not end after the debuggee terminated")
+            .unwrap()
+            .unwrap();
+
+        let steps: Vec<_> = adapter
+            .received_commands()
+            .into_iter()
+            .filter(|command| matches!(command.as_str(), "next" | "stepIn"))
+            .collect();
+        assert_eq!(steps, ["next", "stepIn"]);
+
+        let step = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "next")
+            .unwrap();
+        assert_eq!(step["arguments"]["threadId"], 1);
+    }
+
+    #[tokio::test]
+    async fn pauses_attached_process() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let (mut socket, _session) =
+            start_session(&adapter, DebugTarget::Attach { process_id: 4242 }).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let requests = adapter.received_requests();
+        let attach = requests
+            .iter()
+            .find(|request| request["command"] == "attach")
+            .unwrap();
+        assert_eq!(attach["arguments"]["processId"], 4242);
+
+        let pause = requests
+            .iter()
+            .find(|request| request["command"] == "pause")
+            .unwrap();
+        assert_eq!(pause["arguments"]["threadId"], 1);
+        assert!(!adapter.received_commands().contains(&"launch".into()));
+    }
+
+    #[tokio::test]
+    async fn launches_with_arguments_and_environment() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let target = DebugTarget::Launch {
+            executable_path: "/mock/program.dll".into(),
+            args: vec!["--size".into(), "3".into()],
+            cwd: Some("/mock".into()),
+            env: [("GREETING".into(), "hello".into())].into(),
+            stop_at_entry: true,
+            extra_arguments: serde_json::Map::from_iter([("justMyCode".into(), false.into())]),
+        };
+        let (mut socket, _session) = start_session(&adapter, target).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let launch = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "launch")
+            .unwrap();
+        assert_eq!(launch["arguments"]["program"], "/mock/program.dll");
+        assert_eq!(
+            launch["arguments"]["args"],
+            serde_json::json!(["--size", "3"])
+        );
+        assert_eq!(launch["arguments"]["cwd"], "/mock");
+        assert_eq!(launch["arguments"]["env"]["GREETING"], "hello");
+        assert_eq!(launch["arguments"]["justMyCode"], false);
+    }
+
+    #[tokio::test]
+    async fn sets_and_toggles_breakpoints() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .move_breakpoint(12, 14),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let set_breakpoint = serde_json::json!({
+            "request": "set_breakpoint",
+            "file": "/mock/Program.cs",
+            "line": 12,
+            "condition": "count > 2",
+        });
+        socket
+            .send(Message::text(set_breakpoint.to_string()))
+            .await
+            .unwrap();
+
+        let state = next_state_matching(&mut socket, |state| {
+            state["breakpoints"][0]["verified"] == true
+        })
+        .await;
+        assert_eq!(state["breakpoints"][0]["line"], 12);
+        assert_eq!(state["breakpoints"][0]["actual_line"], 14);
+
+        let sent = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "setBreakpoints")
+            .unwrap();
+        assert_eq!(sent["arguments"]["source"]["path"], "/mock/Program.cs");
+        assert_eq!(
+            sent["arguments"]["breakpoints"],
+            serde_json::json!([{ "line": 12, "condition": "count > 2" }])
+        );
+
+        // the breakpoint is drawn where the adapter put it, so that's where it gets toggled off
+        let toggle_breakpoint = serde_json::json!({
+            "request": "toggle_breakpoint",
+            "file": "/mock/Program.cs",
+            "line": 14,
+        });
+        socket
+            .send(Message::text(toggle_breakpoint.to_string()))
+            .await
+            .unwrap();
+
+        next_state_matching(&mut socket, |state| {
+            state["breakpoints"].as_array().is_some_and(Vec::is_empty)
+        })
+        .await;
+
+        let commands = adapter.received_commands();
+        assert_eq!(
+            commands
+                .iter()
+                .filter(|command| *command == "setBreakpoints")
+                .count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn continues_until_paused() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(20).needs_pause()),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| {
+            current_line(state) == Some(10) && state["running"] == false
+        })
+        .await;
+
+        socket.send(thread_request(4, 1)).await.unwrap();
+        next_state_matching(&mut socket, |state| state["running"] == true).await;
+
+        socket.send(thread_request(5, 1)).await.unwrap();
+        next_state_matching(&mut socket, |state| {
+            current_line(state) == Some(20) && state["running"] == false
+        })
+        .await;
+
+        let commands = adapter.received_commands();
+        let continue_index = commands.iter().position(|command| command == "continue");
+        let pause_index = commands.iter().position(|command| command == "pause");
+        assert!(continue_index.is_some() && continue_index < pause_index);
+    }
+
+    #[tokio::test]
+    async fn shows_exception_the_program_stopped_at() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10).exception(
+            "System.DivideByZeroException",
+            "Attempted to divide by zero.",
+        )));
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        assert_eq!(
+            state["exception"]["type_name"],
+            "System.DivideByZeroException"
+        );
+        assert_eq!(
+            state["exception"]["message"],
+            "Attempted to divide by zero."
+        );
+        assert!(
+            state["exception"]["stack_trace"]
+                .as_str()
+                .unwrap()
+                .contains("Program.Add")
+        );
+
+        let set_exception_breakpoints = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "setExceptionBreakpoints")
+            .unwrap();
+        assert_eq!(
+            set_exception_breakpoints["arguments"]["filters"],
+            serde_json::json!(["all", "user-unhandled"])
+        );
+
+        let commands = adapter.received_commands();
+        let position = |command: &str| commands.iter().position(|sent| sent == command);
+        assert!(position("setExceptionBreakpoints") < position("configurationDone"));
+    }
+
+    #[tokio::test]
+    async fn sets_function_breakpoints_before_program_starts() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let state_machine = DapStateMachine::new(DebugAdapter::NetCoreDbg, launch_target())
+            .with_function_breakpoints([FunctionBreakpoint {
+                name: "Program.Factorial".into(),
+                condition: Some("n == 1".into()),
+            }]);
+        let (mut socket, _session) = start_session_with(&adapter, state_machine).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        assert_eq!(
+            state["function_breakpoints"],
+            serde_json::json!([{
+                "name": "Program.Factorial",
+                "condition": "n == 1",
+                "verified": true,
+                "message": null,
+            }])
+        );
+
+        let commands = adapter.received_commands();
+        let position = |command: &str| commands.iter().position(|sent| sent == command);
+        assert!(position("setFunctionBreakpoints") < position("configurationDone"));
+
+        let clear_function_breakpoint = serde_json::json!({
+            "request": "clear_function_breakpoint",
+            "name": "Program.Factorial",
+        });
+        socket
+            .send(Message::text(clear_function_breakpoint.to_string()))
+            .await
+            .unwrap();
+
+        next_state_matching(&mut socket, |state| {
+            state["function_breakpoints"]
+                .as_array()
+                .is_some_and(Vec::is_empty)
+        })
+        .await;
+
+        let sent = adapter
+            .received_requests()
+            .into_iter()
+            .rfind(|request| request["command"] == "setFunctionBreakpoints")
+            .unwrap();
+        assert_eq!(sent["arguments"]["breakpoints"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn shows_how_watched_variable_changed() {
+        let mut changed = stop_at_line(12);
+        changed.reason = "data breakpoint".into();
+        changed.threads[0].frames[0].scopes[0].variables[1].children[0].value = "5".into();
+
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)).stop(changed));
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let set_data_breakpoint = serde_json::json!({
+            "request": "set_data_breakpoint",
+            "reference": state["heap_variables"][0]["reference"],
+            "name": "X",
+        });
+        socket
+            .send(Message::text(set_data_breakpoint.to_string()))
+            .await
+            .unwrap();
+
+        let state = next_state_matching(&mut socket, |state| {
+            state["data_breakpoints"][0]["verified"] == true
+        })
+        .await;
+        assert_eq!(state["data_breakpoints"][0]["value"], "1");
+
+        socket.send(thread_request(4, 1)).await.unwrap();
+
+        let state = next_state_matching(&mut socket, |state| {
+            current_line(state) == Some(12) && state["running"] == false
+        })
+        .await;
+        assert_eq!(
+            state["data_breakpoints"][0]["hit"],
+            serde_json::json!({ "old_value": "1", "new_value": "5" })
+        );
+        assert_eq!(state["data_breakpoints"][0]["value"], "5");
+
+        let commands = adapter.received_commands();
+        let position = |command: &str| commands.iter().position(|sent| sent == command);
+        assert!(position("dataBreakpointInfo") < position("setDataBreakpoints"));
+    }
+
+    fn run_to_line(line: i64) -> Message {
+        let run_to_line = serde_json::json!({
+            "request": "run_to_line",
+            "file": "/mock/Program.cs",
+            "line": line,
+            "thread": 1,
+        });
+
+        Message::text(run_to_line.to_string())
+    }
+
+    #[tokio::test]
+    async fn runs_to_line_with_temporary_breakpoint() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(20)),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let set_breakpoint = serde_json::json!({
+            "request": "set_breakpoint",
+            "file": "/mock/Program.cs",
+            "line": 30,
+        });
+        socket
+            .send(Message::text(set_breakpoint.to_string()))
+            .await
+            .unwrap();
+        next_state_matching(&mut socket, |state| {
+            state["breakpoints"][0]["verified"] == true
+        })
+        .await;
+
+        socket.send(run_to_line(20)).await.unwrap();
+        let state = next_state_matching(&mut socket, |state| {
+            current_line(state) == Some(20) && state["running"] == false
+        })
+        .await;
+
+        // the user's breakpoint is untouched, the temporary one is never shown
+        assert_eq!(
+            state["breakpoints"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|breakpoint| breakpoint["line"].clone())
+                .collect::<Vec<_>>(),
+            [30]
+        );
+
+        let sent_lines: Vec<_> = adapter
+            .received_requests()
+            .into_iter()
+            .filter(|request| request["command"] == "setBreakpoints")
+            .map(|request| request["arguments"]["breakpoints"].clone())
+            .collect();
+        assert_eq!(
+            sent_lines,
+            [
+                serde_json::json!([{ "line": 30 }]),
+                serde_json::json!([{ "line": 30 }, { "line": 20 }]),
+                serde_json::json!([{ "line": 30 }]),
+            ]
+        );
+
+        let commands = adapter.received_commands();
+        assert!(commands.contains(&"continue".into()));
+        assert!(!commands.contains(&"gotoTargets".into()));
+    }
+
+    #[tokio::test]
+    async fn runs_to_line_with_goto() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .supports_goto()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(20)),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        socket.send(run_to_line(20)).await.unwrap();
+        next_state_matching(&mut socket, |state| current_line(state) == Some(20)).await;
+
+        let goto = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "goto")
+            .unwrap();
+        assert_eq!(goto["arguments"]["threadId"], 1);
+        assert_eq!(goto["arguments"]["targetId"], 20);
+        assert!(
+            !adapter
+                .received_commands()
+                .contains(&"setBreakpoints".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn steps_into_chosen_target() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(
+                    stop_at_line(10)
+                        .step_in_target("Scale")
+                        .step_in_target("Apply"),
+                )
+                .stop(stop_at_line(30)),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        assert_eq!(
+            state["step_in_targets"],
+            serde_json::json!([
+                { "id": 1, "label": "Scale", "line": null },
+                { "id": 2, "label": "Apply", "line": null },
+            ])
+        );
+
+        let step_in = serde_json::json!({
+            "request": "step_in",
+            "thread": 1,
+            "target": 2,
+            "granularity": "instruction",
+        });
+        socket
+            .send(Message::text(step_in.to_string()))
+            .await
+            .unwrap();
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(30)).await;
+        assert!(
+            state["step_in_targets"]
+                .as_array()
+                .is_some_and(Vec::is_empty)
+        );
+
+        let sent = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "stepIn")
+            .unwrap();
+        assert_eq!(sent["arguments"]["targetId"], 2);
+        assert_eq!(sent["arguments"]["granularity"], "instruction");
+    }
+
+    #[tokio::test]
+    async fn restarts_frame() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(8)),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        let frame_id = state["threads"][0]["stack_frames"][1]["id"].clone();
+
+        let restart_frame = serde_json::json!({ "request": "restart_frame", "frame_id": frame_id });
+        socket
+            .send(Message::text(restart_frame.to_string()))
+            .await
+            .unwrap();
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(8)).await;
+
+        let sent = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "restartFrame")
+            .unwrap();
+        assert_eq!(sent["arguments"]["frameId"], frame_id);
+    }
+
+    #[tokio::test]
+    async fn restarts_session_keeping_breakpoints() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(11)),
+        );
+        let (mut socket, session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let set_breakpoint = serde_json::json!({
+            "request": "set_breakpoint",
+            "file": "/mock/Program.cs",
+            "line": 11,
+        });
+        socket
+            .send(Message::text(set_breakpoint.to_string()))
+            .await
+            .unwrap();
+        next_state_matching(&mut socket, |state| {
+            state["breakpoints"][0]["verified"] == true
+        })
+        .await;
+
+        socket.send(thread_request(1, 1)).await.unwrap();
+        next_state_matching(&mut socket, |state| current_line(state) == Some(11)).await;
+
+        let restart_session = serde_json::json!({ "request": "restart_session" });
+        socket
+            .send(Message::text(restart_session.to_string()))
+            .await
+            .unwrap();
+
+        // the same websocket sees the new session stop at the start again
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        assert_eq!(state["breakpoints"][0]["line"], 11);
+        assert_eq!(state["breakpoints"][0]["verified"], true);
+        assert!(!session.is_finished());
+
+        let commands = adapter.received_commands();
+        let restart = commands
+            .iter()
+            .position(|command| command == "terminate")
+            .unwrap();
+        assert_eq!(commands[restart + 1], "initialize");
+        assert!(commands[restart..].contains(&"setBreakpoints".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn restarts_attached_session_without_ending_process() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let (mut socket, _session) =
+            start_session(&adapter, DebugTarget::Attach { process_id: 4242 }).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        let restart_session = serde_json::json!({ "request": "restart_session" });
+        socket
+            .send(Message::text(restart_session.to_string()))
+            .await
+            .unwrap();
+
+        let attaches = || {
+            adapter
+                .received_commands()
+                .iter()
+                .filter(|command| *command == "attach")
+                .count()
+        };
+        next_state_matching(&mut socket, |state| {
+            current_line(state) == Some(10) && attaches() == 2
+        })
+        .await;
+
+        let requests = adapter.received_requests();
+        let disconnect = requests
+            .iter()
+            .find(|request| request["command"] == "disconnect")
+            .unwrap();
+        assert_eq!(disconnect["arguments"]["terminateDebuggee"], false);
+        assert!(!adapter.received_commands().contains(&"terminate".into()));
+    }
+
+    #[tokio::test]
+    async fn shows_thread_that_stopped_and_selects_others() {
+        let worker =
+            MockThread::new(2, "Worker").frame(MockFrame::new("Worker.Run", "/mock/Worker.cs", 40));
+        let adapter = MockAdapter::new(
+            MockScript::new().stop(stop_at_line(10)).stop(
+                MockStop::new("breakpoint")
+                    .thread(MockThread::new(1, "Main Thread").frame(MockFrame::new(
+                        "Program.Main",
+                        "/mock/Program.cs",
+                        11,
+                    )))
+                    .thread(worker)
+                    .stopped_thread(2),
+            ),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        socket.send(thread_request(4, 1)).await.unwrap();
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(40)).await;
+        assert_eq!(state["active_thread"], 2);
+        assert_eq!(state["current_source_file"][0], "/mock/Worker.cs");
+        assert_eq!(state["threads"].as_array().unwrap().len(), 2);
+
+        let select_thread = serde_json::json!({ "request": "select_thread", "thread": 1 });
+        socket
+            .send(Message::text(select_thread.to_string()))
+            .await
+            .unwrap();
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(11)).await;
+        assert_eq!(state["active_thread"], 1);
+    }
+
+    #[tokio::test]
+    async fn queues_steps_requested_while_busy() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .stop(stop_at_line(11))
+                .stop(stop_at_line(12))
+                .stop(stop_at_line(13)),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+
+        // the second and third step arrive while the first one is still being queried
+        for _ in 0..3 {
+            socket.send(thread_request(1, 1)).await.unwrap();
+        }
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(13)).await;
+        assert_eq!(state["queued_user_requests"], 0);
+
+        let steps = adapter
+            .received_commands()
+            .into_iter()
+            .filter(|command| command == "next")
+            .count();
+        assert_eq!(steps, 3);
+    }
+
+    #[tokio::test]
+    async fn sends_final_state_before_closing_websocket() {
+        let adapter = MockAdapter::new(MockScript {
+            exit_code: 3,
+            ..MockScript::new().stop(stop_at_line(10))
+        });
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        socket.send(thread_request(4, 1)).await.unwrap();
+
+        let state = next_state_matching(&mut socket, |state| state["terminated"] == true).await;
+        assert_eq!(state["exit_code"], 3);
+        // the last stop can still be looked at
+        assert_eq!(state["threads"][0]["name"], "Main Thread");
+
+        let close = tokio::time::timeout(TIMEOUT, socket.next())
+            .await
+            .expect("websocket was not closed after the final state")
+            .unwrap()
+            .unwrap();
+        let Message::Close(Some(frame)) = close else {
+            panic!("expected a close frame, got {close:?}");
+        };
+        assert_eq!(u16::from(frame.code), 1000);
+    }
+
+    #[tokio::test]
+    async fn ends_debuggee_on_shutdown() {
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+        let (visualization_state_sender, mut visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (_user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+        let (shutdown_sender, shutdown_receiver) = tokio::sync::watch::channel(false);
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver)
+            .with_shutdown(shutdown_receiver);
+        let connected_adapter = adapter.clone();
+        let session = tokio::spawn(dap_client.run_with(
+            async move || Ok(connected_adapter.start()),
+            DapStateMachine::new(DebugAdapter::NetCoreDbg, launch_target()),
+            TIMEOUT,
+        ));
+
+        tokio::time::timeout(
+            TIMEOUT,
+            visualization_state_receiver
+                .wait_for(|state| current_line(&serde_json::to_value(state).unwrap()) == Some(10)),
+        )
+        .await
+        .expect("program did not stop")
+        .unwrap();
+
+        shutdown_sender.send_replace(true);
+        tokio::time::timeout(TIMEOUT, session)
+            .await
+            .expect("session did not end after shutting down")
+            .unwrap()
+            .unwrap();
+
+        let disconnect = adapter
+            .received_requests()
+            .into_iter()
+            .find(|request| request["command"] == "disconnect")
+            .expect("debug adapter was not disconnected");
+        assert_eq!(disconnect["arguments"]["terminateDebuggee"], true);
+        assert!(visualization_state_receiver.borrow().is_final());
+    }
+
+    #[tokio::test]
+    async fn shows_output_next_to_the_stop_it_led_to() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10).output("stdout", "Hello\n"))
+                .stop(
+                    stop_at_line(11)
+                        .output("stdout", "World")
+                        .output("stderr", "warning\n")
+                        .output("stdout", "!\n"),
+                ),
+        );
+        let (mut socket, _session) = start_session(&adapter, launch_target()).await;
+
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(10)).await;
+        assert_eq!(state["step"], 1);
+        assert_eq!(
+            state["console"],
+            serde_json::json!([{ "category": "stdout", "text": "Hello", "step": 1 }])
+        );
+
+        socket.send(thread_request(4, 1)).await.unwrap();
+        let state = next_state_matching(&mut socket, |state| current_line(state) == Some(11)).await;
+        assert_eq!(state["step"], 2);
+        assert_eq!(
+            state["console"],
+            serde_json::json!([
+                { "category": "stdout", "text": "Hello", "step": 1 },
+                { "category": "stdout", "text": "World!", "step": 2 },
+                { "category": "stderr", "text": "warning", "step": 2 },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_stream_without_dap_headers() {
+        let (client, mut adapter) = tokio::io::duplex(64 * 1024);
+        let (reader, writer) = tokio::io::split(client);
+        let process = DapProcess::from_io(reader, writer);
+
+        adapter.write_all(&[b'x'; 9 * 1024]).await.unwrap();
+
+        let received = tokio::time::timeout(TIMEOUT, process.receive())
+            .await
+            .expect("receiving did not give up");
+        assert!(received.is_err());
+    }
+
+    #[tokio::test]
+    async fn publishes_timed_out_request_once() {
+        let request_timeout = Duration::from_millis(100);
+        // a hanging adapter doesn't answer cancel either
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .never_answer("scopes")
+                .never_answer("cancel"),
+        );
+        let (visualization_state_sender, mut visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (_user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver);
+        let connected_adapter = adapter.clone();
+        tokio::spawn(dap_client.run_with(
+            async move || Ok(connected_adapter.start()),
+            DapStateMachine::new(DebugAdapter::NetCoreDbg, launch_target()),
+            request_timeout,
+        ));
+
+        let mut failure_states = Vec::new();
+        // long enough for an unanswered cancel to time out several times over
+        while let Ok(Ok(())) =
+            tokio::time::timeout(request_timeout * 10, visualization_state_receiver.changed()).await
+        {
+            let state =
+                serde_json::to_value(&*visualization_state_receiver.borrow_and_update()).unwrap();
+            if state["request_failures"] != serde_json::json!([]) {
+                failure_states.push(state);
+            }
+        }
+
+        // published once, without failures of the cancel requests
+        assert_eq!(failure_states.len(), 1);
+        let failed_commands: Vec<_> = failure_states[0]["request_failures"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|failure| failure["command"].as_str().unwrap())
+            .collect();
+        assert_eq!(failed_commands, ["scopes", "scopes"]);
+        assert_eq!(current_line(&failure_states[0]), Some(10));
+
+        let commands = adapter.received_commands();
+        let count = |name: &str| commands.iter().filter(|command| *command == name).count();
+        assert_eq!(count("cancel"), count("scopes"));
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_session() {
+        let trace = std::env::temp_dir().join(format!("dapviz-trace-{}.jsonl", std::process::id()));
+        let adapter = MockAdapter::new(MockScript::new().stop(stop_at_line(10)));
+
+        let (visualization_state_sender, mut visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (_user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+        let (shutdown_sender, shutdown_receiver) = tokio::sync::watch::channel(false);
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver)
+            .with_shutdown(shutdown_receiver);
+        let recorded_trace = trace.clone();
+        let session = tokio::spawn(dap_client.run_with(
+            async move || {
+                Ok(adapter
+                    .start()
+                    .record_to(DapRecorder::create(&recorded_trace)?))
+            },
+            DapStateMachine::new(DebugAdapter::NetCoreDbg, launch_target()),
+            TIMEOUT,
+        ));
+
+        tokio::time::timeout(
+            TIMEOUT,
+            visualization_state_receiver
+                .wait_for(|state| current_line(&serde_json::to_value(state).unwrap()) == Some(10)),
+        )
+        .await
+        .expect("program did not stop")
+        .unwrap();
+
+        shutdown_sender.send_replace(true);
+        session.await.unwrap().unwrap();
+
+        let replay = DapReplay::load(&trace).unwrap();
+        std::fs::remove_file(&trace).unwrap();
+        let replay_adapter = replay.debug_adapter().unwrap();
+        let replay_target = replay.target().unwrap();
+        let DebugTarget::Launch {
+            executable_path, ..
+        } = &replay_target
+        else {
+            panic!("expected a launch target, got {replay_target:?}");
+        };
+        assert_eq!(executable_path, "/mock/program.dll");
+
+        let (visualization_state_sender, mut visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (_user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver);
+        tokio::spawn(dap_client.run_with_process(
+            replay.start(),
+            DapStateMachine::new(replay_adapter, replay_target),
+            TIMEOUT,
+        ));
+
+        let state = tokio::time::timeout(
+            TIMEOUT,
+            visualization_state_receiver
+                .wait_for(|state| current_line(&serde_json::to_value(state).unwrap()) == Some(10)),
+        )
+        .await
+        .expect("replay did not stop where the recording did")
+        .map(|state| serde_json::to_value(&*state).unwrap())
+        .unwrap();
+        assert_eq!(state["threads"][0]["name"], "Main Thread");
+    }
+
+    #[tokio::test]
+    async fn ends_session_when_configuration_fails() {
+        let adapter = MockAdapter::new(
+            MockScript::new()
+                .stop(stop_at_line(10))
+                .never_answer("configurationDone"),
+        );
+        let (visualization_state_sender, mut visualization_state_receiver) =
+            tokio::sync::watch::channel(VisualizationState::default());
+        let (_user_request_sender, user_request_receiver) =
+            tokio::sync::broadcast::channel::<UserRequest>(64);
+
+        let dap_client = DapClient::new(visualization_state_sender, user_request_receiver);
+        let session = tokio::spawn(dap_client.run_with(
+            async move || Ok(adapter.start()),
+            DapStateMachine::new(DebugAdapter::NetCoreDbg, launch_target()),
+            Duration::from_millis(100),
+        ));
+
+        tokio::time::timeout(TIMEOUT, session)
+            .await
+            .expect("session did not end")
+            .unwrap()
+            .unwrap();
+
+        let state =
+            serde_json::to_value(&*visualization_state_receiver.borrow_and_update()).unwrap();
+        assert_eq!(state["terminated"], true);
+        assert_eq!(state["request_failures"][0]["command"], "configurationDone");
     }
 }
@@ -0,0 +1,341 @@
+use anyhow::Context as _;
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use dap_types::types::ProtocolMessage;
+
+const CONTENT_LENGTH_HEADER: &str = "Content-Length";
+const HEADER_TERMINATOR: &[u8] = b"\r\n\r\n";
+
+// headers are tiny in practice, if we haven't seen the end of them after this many bytes the
+// stream is most likely not speaking dap
+const MAX_HEADER_LENGTH: usize = 8 * 1024;
+
+// far more than the biggest variables response, a bigger claimed length is garbage or malicious
+// and must not make us allocate it
+const MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+// how much room is made for the content of a message ahead of reading it
+const MAX_RESERVE_LENGTH: usize = 64 * 1024;
+
+/// Encoder and decoder for the base protocol of the debug adapter protocol.
+///
+/// Every message consists of a header part and a content part, separated by an empty line. The
+/// header part is a list of `name: value` fields terminated by `\r\n`, of which only
+/// `Content-Length` is required. Unknown headers are accepted and ignored.
+///
+/// See: https://microsoft.github.io/debug-adapter-protocol/overview#base-protocol
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DapCodec;
+
+impl DapCodec {
+    pub fn encode(&self, message: &ProtocolMessage, destination: &mut BytesMut) {
+        let payload = serde_json::to_vec(message)
+            .expect("dap message should not contain map with non-string key");
+
+        self.encode_frame(&payload, destination);
+    }
+
+    pub fn encode_frame(&self, payload: &[u8], destination: &mut BytesMut) {
+        let header = format!("{CONTENT_LENGTH_HEADER}: {}\r\n\r\n", payload.len());
+
+        destination.reserve(header.len() + payload.len());
+        destination.put_slice(header.as_bytes());
+        destination.put_slice(payload);
+    }
+
+    /// Decodes the next message from the front of `source`.
+    ///
+    /// Returns `Ok(None)` if `source` does not hold a complete message yet, in which case nothing
+    /// is consumed and the call should be repeated once more bytes have been appended. On
+    /// success, exactly the bytes of the returned message are consumed.
+    pub fn decode(&self, source: &mut BytesMut) -> anyhow::Result<Option<ProtocolMessage>> {
+        let Some(frame) = self.decode_frame(source)? else {
+            return Ok(None);
+        };
+
+        let message = serde_json::from_slice(&frame).context("could not parse dap message")?;
+
+        Ok(Some(message))
+    }
+
+    /// Like [`DapCodec::decode`], but returns the raw content part instead of parsing it.
+    ///
+    /// Malformed headers are consumed up to and including the empty line that ends them, so the
+    /// caller may report the error and continue decoding the rest of the stream.
+    pub fn decode_frame(&self, source: &mut BytesMut) -> anyhow::Result<Option<Bytes>> {
+        let Some(header_length) = find(source, HEADER_TERMINATOR) else {
+            anyhow::ensure!(
+                source.len() <= MAX_HEADER_LENGTH,
+                "no end of dap headers found in the first {MAX_HEADER_LENGTH} bytes"
+            );
+
+            return Ok(None);
+        };
+
+        let content_length = match parse_content_length(&source[..header_length]) {
+            Ok(content_length) if content_length <= MAX_CONTENT_LENGTH => content_length,
+            Ok(content_length) => {
+                source.advance(header_length + HEADER_TERMINATOR.len());
+                anyhow::bail!(
+                    "content length {content_length} exceeds the maximum of {MAX_CONTENT_LENGTH}"
+                );
+            }
+            Err(err) => {
+                source.advance(header_length + HEADER_TERMINATOR.len());
+                return Err(err);
+            }
+        };
+
+        let frame_length = header_length
+            .checked_add(HEADER_TERMINATOR.len())
+            .and_then(|length| length.checked_add(content_length))
+            .context("dap message length overflows")?;
+        if source.len() < frame_length {
+            source.reserve((frame_length - source.len()).min(MAX_RESERVE_LENGTH));
+            return Ok(None);
+        }
+
+        source.advance(header_length + HEADER_TERMINATOR.len());
+
+        Ok(Some(source.split_to(content_length).freeze()))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn parse_content_length(headers: &[u8]) -> anyhow::Result<usize> {
+    let headers = std::str::from_utf8(headers).context("dap headers are not valid utf-8")?;
+
+    let mut content_length = None;
+
+    for header in headers.split("\r\n") {
+        let (name, value) = header
+            .split_once(':')
+            .with_context(|| format!("malformed dap header: {header:?}"))?;
+
+        if !name.trim().eq_ignore_ascii_case(CONTENT_LENGTH_HEADER) {
+            continue;
+        }
+
+        let length = value
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("invalid content length: {value:?}"))?;
+
+        anyhow::ensure!(
+            content_length.is_none_or(|previous| previous == length),
+            "conflicting content length headers"
+        );
+
+        content_length = Some(length);
+    }
+
+    content_length.context("dap message is missing the content length header")
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use dap_types::types::ProtocolMessage;
+    use proptest::prelude::*;
+
+    use super::DapCodec;
+
+    fn output_event(seq: usize, output: &str) -> ProtocolMessage {
+        serde_json::from_value(serde_json::json!({
+            "seq": seq,
+            "type": "event",
+            "event": "output",
+            "body": { "category": "stdout", "output": output },
+        }))
+        .unwrap()
+    }
+
+    fn to_json(message: &ProtocolMessage) -> String {
+        serde_json::to_string(message).unwrap()
+    }
+
+    fn decode_all(codec: &DapCodec, buffer: &mut BytesMut) -> Vec<ProtocolMessage> {
+        std::iter::from_fn(|| codec.decode(buffer).unwrap()).collect()
+    }
+
+    /// feeds `bytes` to the codec in chunks and returns every message decoded along the way
+    fn decode_chunked(bytes: &[u8], chunk_lengths: &[usize]) -> Vec<ProtocolMessage> {
+        let codec = DapCodec;
+        let mut buffer = BytesMut::new();
+        let mut decoded = Vec::new();
+        let mut remaining = bytes;
+
+        for chunk_length in chunk_lengths.iter().copied().chain(std::iter::repeat(1)) {
+            if remaining.is_empty() {
+                break;
+            }
+
+            let (chunk, rest) = remaining.split_at(chunk_length.clamp(1, remaining.len()));
+            remaining = rest;
+
+            buffer.extend_from_slice(chunk);
+            decoded.extend(decode_all(&codec, &mut buffer));
+        }
+
+        assert!(buffer.is_empty(), "decoder left unconsumed bytes behind");
+
+        decoded
+    }
+
+    #[test]
+    fn decodes_message_with_additional_headers() {
+        let payload = to_json(&output_event(1, "hello"));
+        let mut buffer = BytesMut::from(
+            format!(
+                "Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length:{}\r\n\r\n{payload}",
+                payload.len()
+            )
+            .as_bytes(),
+        );
+
+        let decoded = DapCodec.decode(&mut buffer).unwrap().unwrap();
+
+        assert_eq!(to_json(&decoded), payload);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn decodes_one_message_at_a_time() {
+        let codec = DapCodec;
+        let mut buffer = BytesMut::new();
+        codec.encode(&output_event(1, "first"), &mut buffer);
+        codec.encode(&output_event(2, "second"), &mut buffer);
+
+        let first = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(first.seq, 1);
+        assert!(!buffer.is_empty());
+
+        let second = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(second.seq, 2);
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+    }
+
+    #[test]
+    fn recovers_after_malformed_header() {
+        let codec = DapCodec;
+        let mut buffer = BytesMut::from(&b"Content-Length: nope\r\n\r\n"[..]);
+        codec.encode(&output_event(7, "after"), &mut buffer);
+
+        assert!(codec.decode(&mut buffer).is_err());
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap().seq, 7);
+    }
+
+    #[test]
+    fn rejects_missing_content_length() {
+        let mut buffer = BytesMut::from(&b"Content-Type: application/json\r\n\r\n{}"[..]);
+
+        assert!(DapCodec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn rejects_endless_headers() {
+        let mut buffer = BytesMut::from(vec![b'a'; 16 * 1024].as_slice());
+
+        assert!(DapCodec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_content_length() {
+        for content_length in ["18446744073709551615", "4294967296"] {
+            let mut buffer =
+                BytesMut::from(format!("Content-Length: {content_length}\r\n\r\n{{}}").as_bytes());
+
+            let err = DapCodec.decode(&mut buffer).unwrap_err();
+            assert!(err.to_string().contains("exceeds the maximum"));
+            // the header is skipped like any other malformed one
+            assert_eq!(&buffer[..], b"{}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrips_across_arbitrary_read_boundaries(
+            outputs in prop::collection::vec(any::<String>(), 1..8),
+            chunk_lengths in prop::collection::vec(1usize..64, 0..256),
+        ) {
+            let codec = DapCodec;
+            let messages: Vec<_> = outputs
+                .iter()
+                .enumerate()
+                .map(|(seq, output)| output_event(seq, output))
+                .collect();
+
+            let mut encoded = BytesMut::new();
+            for message in &messages {
+                codec.encode(message, &mut encoded);
+            }
+
+            let decoded = decode_chunked(&encoded, &chunk_lengths);
+
+            prop_assert_eq!(
+                decoded.iter().map(to_json).collect::<Vec<_>>(),
+                messages.iter().map(to_json).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn accepts_any_set_of_extra_headers(
+            output in any::<String>(),
+            extra_headers in prop::collection::vec(("[A-Za-z][A-Za-z-]{0,16}", "[ -~]{0,32}"), 0..4),
+            content_length_position in any::<prop::sample::Index>(),
+            split in any::<prop::sample::Index>(),
+        ) {
+            let payload = to_json(&output_event(1, &output));
+
+            let mut headers: Vec<_> = extra_headers
+                .into_iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("content-length"))
+                .map(|(name, value)| format!("{name}: {value}"))
+                .collect();
+            let position = content_length_position.index(headers.len() + 1);
+            headers.insert(position, format!("Content-Length: {}", payload.len()));
+
+            let frame = format!("{}\r\n\r\n{payload}", headers.join("\r\n"));
+            let split = split.index(frame.len());
+
+            let decoded = decode_chunked(frame.as_bytes(), &[split.max(1)]);
+
+            prop_assert_eq!(decoded.len(), 1);
+            prop_assert_eq!(to_json(&decoded[0]), payload);
+        }
+
+        #[test]
+        fn incomplete_frames_are_not_consumed(output in any::<String>(), cut in any::<prop::sample::Index>()) {
+            let codec = DapCodec;
+            let mut encoded = BytesMut::new();
+            codec.encode(&output_event(1, &output), &mut encoded);
+
+            let cut = cut.index(encoded.len());
+            let mut partial = BytesMut::from(&encoded[..cut]);
+
+            prop_assert!(codec.decode(&mut partial).unwrap().is_none());
+            prop_assert_eq!(&partial[..], &encoded[..cut]);
+        }
+
+        #[test]
+        fn never_panics_on_garbage(garbage in prop::collection::vec(any::<u8>(), 0..4096)) {
+            let codec = DapCodec;
+            let mut buffer = BytesMut::from(garbage.as_slice());
+
+            // every call must either consume bytes, report an incomplete message or fail
+            loop {
+                let length_before = buffer.len();
+                match codec.decode_frame(&mut buffer) {
+                    Ok(Some(_)) => prop_assert!(buffer.len() < length_before),
+                    Ok(None) => break,
+                    Err(_) if buffer.len() < length_before => continue,
+                    Err(_) => break,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write as _},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DapTraceDirection {
+    /// from dapviz to the debug adapter
+    Sent,
+    /// from the debug adapter to dapviz
+    Received,
+}
+
+/// One line of a dap trace file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DapTraceEntry {
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub direction: DapTraceDirection,
+    /// the message as exchanged, kept as a string if it isn't valid json
+    pub message: Value,
+}
+
+/// Writes every message exchanged with the debug adapter to a json lines file.
+///
+/// Writing is synchronous on purpose: recording must not add an await point between decoding a
+/// message and handing it out, otherwise receiving would no longer be cancel safe.
+pub struct DapRecorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl DapRecorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("could not create dap trace file {}", path.display()))?;
+
+        Ok(DapRecorder {
+            file: BufWriter::new(file).into(),
+        })
+    }
+
+    /// Records the content part of a message, exactly as it was sent or received.
+    pub fn record(&self, direction: DapTraceDirection, payload: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as u64);
+
+        let message = serde_json::from_slice(payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).into_owned()));
+
+        let entry = DapTraceEntry {
+            timestamp,
+            direction,
+            message,
+        };
+
+        let mut line = serde_json::to_vec(&entry).expect("json values should serialize");
+        line.push(b'\n');
+
+        // flush every line, the trace is most interesting when dapviz or the adapter crashed
+        let mut file = self
+            .file
+            .lock()
+            .expect("dap trace file lock should not be poisoned");
+
+        if let Err(err) = file.write_all(&line).and_then(|_| file.flush()) {
+            tracing::error!("Could not write to dap trace file: {err}");
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use bytes::BytesMut;
+use clap::ValueEnum as _;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+
+use crate::{
+    dap_client::DapProcess,
+    dap_codec::DapCodec,
+    dap_recorder::{DapTraceDirection, DapTraceEntry},
+    dap_states::dap_state_machine::DebugTarget,
+    debug_adapters::DebugAdapter,
+};
+
+/// A fake debug adapter that answers requests from a trace recorded with `--record-dap`.
+///
+/// Requests are matched to the first unanswered recorded request with equal arguments, falling
+/// back to the first one with the same command, and answered with the recorded response. Events
+/// and reverse requests are sent once every request that preceded them in the recording has been
+/// answered, so they arrive in the same order relative to the requests as they originally did.
+pub struct DapReplay {
+    messages: Vec<(DapTraceDirection, Value)>,
+    // whether a sent message was matched to a request, or a received message was replayed
+    replayed: Vec<bool>,
+}
+
+impl DapReplay {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let trace = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read dap trace {}", path.display()))?;
+
+        let messages = trace
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str::<DapTraceEntry>(line)
+                    .map(|entry| (entry.direction, entry.message))
+                    .with_context(|| format!("invalid dap trace entry on line {}", i + 1))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(DapReplay::from_messages(messages))
+    }
+
+    pub fn from_messages(messages: impl IntoIterator<Item = (DapTraceDirection, Value)>) -> Self {
+        let messages: Vec<_> = messages.into_iter().collect();
+
+        DapReplay {
+            replayed: vec![false; messages.len()],
+            messages,
+        }
+    }
+
+    /// The debug adapter the trace was recorded with.
+    pub fn debug_adapter(&self) -> anyhow::Result<DebugAdapter> {
+        let adapter_id = self
+            .recorded_requests()
+            .find_map(|request| match request["command"].as_str() {
+                Some("initialize") => request["arguments"]["adapterID"].as_str(),
+                _ => None,
+            })
+            .context("dap trace does not contain an initialize request")?;
+
+        DebugAdapter::value_variants()
+            .iter()
+            .find(|adapter| adapter.adapter_id() == adapter_id)
+            .cloned()
+            .with_context(|| format!("dap trace was recorded with unknown adapter {adapter_id}"))
+    }
+
+    /// What was debugged in the recorded session.
+    pub fn target(&self) -> anyhow::Result<DebugTarget> {
+        self.recorded_requests()
+            .find_map(|request| {
+                let arguments = &request["arguments"];
+
+                match request["command"].as_str()? {
+                    "launch" => Some(DebugTarget::Launch {
+                        executable_path: arguments["program"].as_str()?.to_owned(),
+                        args: serde_json::from_value(arguments["args"].clone()).unwrap_or_default(),
+                        cwd: arguments["cwd"]
+                            .as_str()
+                            .filter(|cwd| !cwd.is_empty())
+                            .map(str::to_owned),
+                        env: serde_json::from_value(arguments["env"].clone()).unwrap_or_default(),
+                        stop_at_entry: arguments["stopAtEntry"].as_bool().unwrap_or(true),
+                        extra_arguments: arguments
+                            .as_object()?
+                            .iter()
+                            .filter(|(key, _)| {
+                                !matches!(
+                                    key.as_str(),
+                                    "program" | "args" | "cwd" | "env" | "stopAtEntry"
+                                )
+                            })
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect(),
+                    }),
+                    "attach" => Some(DebugTarget::Attach {
+                        process_id: arguments["processId"].as_u64()?.try_into().ok()?,
+                    }),
+                    _ => None,
+                }
+            })
+            .context("dap trace does not contain a launch or attach request")
+    }
+
+    /// Starts answering requests in the background and returns the connection to it.
+    pub fn start(self) -> DapProcess {
+        let (client, adapter) = tokio::io::duplex(64 * 1024);
+        let (adapter_reader, adapter_writer) = tokio::io::split(adapter);
+        let (client_reader, client_writer) = tokio::io::split(client);
+
+        tokio::spawn(async move {
+            if let Err(err) = self.serve(adapter_reader, adapter_writer).await {
+                tracing::error!("DAP replay stopped: {err:#}");
+            }
+        });
+
+        DapProcess::from_io(client_reader, client_writer)
+    }
+
+    async fn serve(
+        mut self,
+        mut reader: impl AsyncRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> anyhow::Result<()> {
+        let codec = DapCodec;
+        let mut read_buffer = BytesMut::new();
+        let mut write_buffer = BytesMut::new();
+
+        for message in self.release_events() {
+            codec.encode_frame(&serde_json::to_vec(&message)?, &mut write_buffer);
+        }
+
+        loop {
+            writer.write_all(&write_buffer).await?;
+            writer.flush().await?;
+            write_buffer.clear();
+
+            let frame = loop {
+                if let Some(frame) = codec.decode_frame(&mut read_buffer)? {
+                    break frame;
+                }
+
+                if reader.read_buf(&mut read_buffer).await? == 0 {
+                    // dapviz hung up, nothing left to replay
+                    return Ok(());
+                }
+            };
+
+            let message: Value =
+                serde_json::from_slice(&frame).context("could not parse dap message")?;
+
+            // we never send reverse requests, so only requests need an answer
+            if message["type"] != "request" {
+                continue;
+            }
+
+            for reply in self.answer(&message) {
+                codec.encode_frame(&serde_json::to_vec(&reply)?, &mut write_buffer);
+            }
+        }
+    }
+
+    fn answer(&mut self, request: &Value) -> Vec<Value> {
+        let seq = request["seq"].clone();
+        let command = request["command"].as_str().unwrap_or("unknown").to_owned();
+
+        let recorded_request = self
+            .find_recorded_request(|recorded| {
+                recorded["command"] == command && recorded["arguments"] == request["arguments"]
+            })
+            .or_else(|| self.find_recorded_request(|recorded| recorded["command"] == command));
+
+        let response = match recorded_request {
+            Some((index, recorded_seq)) => {
+                self.replayed[index] = true;
+                self.take_recorded_response(&recorded_seq)
+            }
+            None => None,
+        };
+
+        let mut response = response.unwrap_or_else(|| {
+            tracing::warn!("No recorded response for request {seq} ({command})");
+
+            serde_json::json!({
+                "seq": 0,
+                "type": "response",
+                "success": false,
+                "command": command,
+                "message": "request does not match the recorded dap trace",
+            })
+        });
+
+        response["request_seq"] = seq;
+
+        let mut replies = vec![response];
+        replies.extend(self.release_events());
+        replies
+    }
+
+    fn recorded_requests(&self) -> impl Iterator<Item = &Value> {
+        self.messages
+            .iter()
+            .filter(|(direction, _)| *direction == DapTraceDirection::Sent)
+            .map(|(_, message)| message)
+    }
+
+    /// Returns the index and seq of the first unmatched recorded request fulfilling `predicate`.
+    fn find_recorded_request(&self, predicate: impl Fn(&Value) -> bool) -> Option<(usize, Value)> {
+        self.messages
+            .iter()
+            .enumerate()
+            .find(|(index, (direction, message))| {
+                !self.replayed[*index]
+                    && *direction == DapTraceDirection::Sent
+                    && predicate(message)
+            })
+            .map(|(index, (_, message))| (index, message["seq"].clone()))
+    }
+
+    fn take_recorded_response(&mut self, recorded_seq: &Value) -> Option<Value> {
+        let index =
+            self.messages
+                .iter()
+                .enumerate()
+                .position(|(index, (direction, message))| {
+                    !self.replayed[index]
+                        && *direction == DapTraceDirection::Received
+                        && message["type"] == "response"
+                        && message["request_seq"] == *recorded_seq
+                })?;
+
+        self.replayed[index] = true;
+        Some(self.messages[index].1.clone())
+    }
+
+    /// Replays every event and reverse request that is no longer waiting for a request to be
+    /// answered first.
+    fn release_events(&mut self) -> Vec<Value> {
+        let mut released = Vec::new();
+
+        for (index, (direction, message)) in self.messages.iter().enumerate() {
+            if self.replayed[index] {
+                continue;
+            }
+
+            match direction {
+                // cancel requests only happen after timeouts, which don't happen during replay
+                DapTraceDirection::Sent if message["command"] == "cancel" => (),
+                DapTraceDirection::Sent => break,
+                DapTraceDirection::Received if message["type"] == "response" => (),
+                DapTraceDirection::Received => {
+                    self.replayed[index] = true;
+                    released.push(message.clone());
+                }
+            }
+        }
+
+        released
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use dap_types::types::{ProtocolMessage, ProtocolMessageType, ResponseResult};
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    use super::DapReplay;
+    use crate::{
+        dap_codec::DapCodec, dap_recorder::DapTraceDirection,
+        dap_states::dap_state_machine::DebugTarget, debug_adapters::DebugAdapter,
+    };
+
+    fn recorded_session() -> DapReplay {
+        use DapTraceDirection::{Received, Sent};
+
+        DapReplay::from_messages([
+            (
+                Sent,
+                serde_json::json!({"seq": 1, "type": "request", "command": "initialize", "arguments": {"adapterID": "coreclr"}}),
+            ),
+            (
+                Received,
+                serde_json::json!({"seq": 1, "type": "response", "request_seq": 1, "success": true, "command": "initialize", "body": {}}),
+            ),
+            (
+                Received,
+                serde_json::json!({"seq": 2, "type": "event", "event": "initialized"}),
+            ),
+            (
+                Sent,
+                serde_json::json!({"seq": 2, "type": "request", "command": "launch", "arguments": {"program": "/recorded/program.dll"}}),
+            ),
+            (
+                Received,
+                serde_json::json!({"seq": 3, "type": "response", "request_seq": 2, "success": true, "command": "launch"}),
+            ),
+            (
+                Received,
+                serde_json::json!({"seq": 4, "type": "event", "event": "stopped", "body": {"reason": "entry", "threadId": 1}}),
+            ),
+        ])
+    }
+
+    async fn exchange(
+        stream: &mut tokio::io::DuplexStream,
+        buffer: &mut BytesMut,
+        request: serde_json::Value,
+        expected_messages: usize,
+    ) -> Vec<ProtocolMessage> {
+        let codec = DapCodec;
+
+        let mut encoded = BytesMut::new();
+        codec.encode_frame(&serde_json::to_vec(&request).unwrap(), &mut encoded);
+        stream.write_all(&encoded).await.unwrap();
+
+        let mut messages = Vec::new();
+        while messages.len() < expected_messages {
+            match codec.decode(buffer).unwrap() {
+                Some(message) => messages.push(message),
+                None => assert_ne!(stream.read_buf(buffer).await.unwrap(), 0),
+            }
+        }
+
+        messages
+    }
+
+    #[test]
+    fn session_info_is_read_from_trace() {
+        let replay = recorded_session();
+
+        assert_eq!(replay.debug_adapter().unwrap(), DebugAdapter::NetCoreDbg);
+        assert_eq!(
+            replay.target().unwrap(),
+            DebugTarget::launch("/recorded/program.dll")
+        );
+    }
+
+    #[test]
+    fn attach_target_keeps_process_id() {
+        let replay = DapReplay::from_messages([(
+            DapTraceDirection::Sent,
+            serde_json::json!({"seq": 2, "type": "request", "command": "attach", "arguments": {"processId": 4242, "justMyCode": true}}),
+        )]);
+
+        assert_eq!(
+            replay.target().unwrap(),
+            DebugTarget::Attach { process_id: 4242 }
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_requests_from_trace() {
+        let (mut client, adapter) = tokio::io::duplex(4096);
+        let (adapter_reader, adapter_writer) = tokio::io::split(adapter);
+        tokio::spawn(recorded_session().serve(adapter_reader, adapter_writer));
+
+        let mut buffer = BytesMut::new();
+
+        let initialize = exchange(
+            &mut client,
+            &mut buffer,
+            serde_json::json!({"seq": 10, "type": "request", "command": "initialize", "arguments": {"adapterID": "coreclr"}}),
+            2,
+        )
+        .await;
+
+        assert!(matches!(
+            &initialize[0].type_,
+            ProtocolMessageType::Response(response) if response.request_seq == 10 && response.success
+        ));
+        assert!(matches!(
+            initialize[1].type_,
+            ProtocolMessageType::Event(..)
+        ));
+
+        // arguments differ from the recording, matched by command instead
+        let launch = exchange(
+            &mut client,
+            &mut buffer,
+            serde_json::json!({"seq": 11, "type": "request", "command": "launch", "arguments": {"program": "/elsewhere/program.dll"}}),
+            2,
+        )
+        .await;
+
+        assert!(matches!(
+            &launch[0].type_,
+            ProtocolMessageType::Response(response) if response.request_seq == 11
+        ));
+        assert!(matches!(launch[1].type_, ProtocolMessageType::Event(..)));
+
+        let unknown = exchange(
+            &mut client,
+            &mut buffer,
+            serde_json::json!({"seq": 12, "type": "request", "command": "threads"}),
+            1,
+        )
+        .await;
+
+        assert!(matches!(
+            &unknown[0].type_,
+            ProtocolMessageType::Response(response)
+                if response.request_seq == 12
+                    && !response.success
+                    && matches!(response.result, ResponseResult::Error { .. })
+        ));
+    }
+}
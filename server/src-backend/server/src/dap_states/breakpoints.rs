@@ -0,0 +1,654 @@
+use std::collections::BTreeMap;
+
+use dap_types::types::{
+    Breakpoint, BreakpointEventBody, DataBreakpoint, SourceBreakpoint, StoppedEventBody,
+};
+use serde::Serialize;
+
+use crate::{
+    dap_states::dap_state_machine::ProgramState,
+    user_request::{FunctionBreakpoint, LineBreakpoint},
+};
+
+/// A line breakpoint along with what the debug adapter made of it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BreakpointInfo {
+    #[serde(flatten)]
+    pub requested: LineBreakpoint,
+    /// assigned by the debug adapter, used to match `breakpoint` events
+    #[serde(skip)]
+    pub id: Option<i64>,
+    pub verified: bool,
+    /// where the debug adapter actually put the breakpoint, e.g. the next line with code on it
+    pub actual_line: Option<i64>,
+    /// why the breakpoint could not be verified, if the debug adapter told us
+    pub message: Option<String>,
+}
+
+impl BreakpointInfo {
+    fn new(requested: LineBreakpoint) -> Self {
+        BreakpointInfo {
+            requested,
+            id: None,
+            verified: false,
+            actual_line: None,
+            message: None,
+        }
+    }
+
+    fn is_on_line(&self, line: i64) -> bool {
+        self.requested.line == line || self.actual_line == Some(line)
+    }
+
+    fn update(&mut self, breakpoint: &Breakpoint) {
+        self.id = breakpoint.id.or(self.id);
+        self.verified = breakpoint.verified;
+        self.actual_line = breakpoint.line.or(self.actual_line);
+        self.message = breakpoint.message.clone();
+    }
+}
+
+/// A function breakpoint along with what the debug adapter made of it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FunctionBreakpointInfo {
+    #[serde(flatten)]
+    pub requested: FunctionBreakpoint,
+    /// assigned by the debug adapter, used to match `breakpoint` events
+    #[serde(skip)]
+    pub id: Option<i64>,
+    pub verified: bool,
+    /// why the breakpoint could not be verified, if the debug adapter told us
+    pub message: Option<String>,
+}
+
+impl FunctionBreakpointInfo {
+    fn update(&mut self, breakpoint: &Breakpoint) {
+        self.id = breakpoint.id.or(self.id);
+        self.verified = breakpoint.verified;
+        self.message = breakpoint.message.clone();
+    }
+}
+
+/// How a watched variable changed, shown when the program stopped because of it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DataBreakpointHit {
+    pub old_value: String,
+    pub new_value: String,
+}
+
+/// A data breakpoint, stopping whenever the watched variable changes.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DataBreakpointInfo {
+    /// identifies the watched data, also used to clear the breakpoint
+    pub data_id: String,
+    /// what the debug adapter says is watched
+    pub description: String,
+    /// name of the watched variable
+    pub name: String,
+    /// value of the watched variable when the program last stopped
+    pub value: String,
+    /// memory reference of the watched variable, used to find it again whenever the program
+    /// stopped, as variables references are only valid until it continues
+    #[serde(skip)]
+    pub address: String,
+    /// assigned by the debug adapter, used to match `breakpoint` and `stopped` events
+    #[serde(skip)]
+    pub id: Option<i64>,
+    pub verified: bool,
+    /// why the breakpoint could not be verified, if the debug adapter told us
+    pub message: Option<String>,
+    /// set if the program is stopped because the watched variable changed
+    pub hit: Option<DataBreakpointHit>,
+    /// the program stopped because of this breakpoint, but the new value was not queried yet
+    #[serde(skip)]
+    hit_pending: bool,
+}
+
+impl DataBreakpointInfo {
+    pub fn new(data_id: String, description: String, name: String, value: String) -> Self {
+        DataBreakpointInfo {
+            data_id,
+            description,
+            name,
+            value,
+            address: String::new(),
+            id: None,
+            verified: false,
+            message: None,
+            hit: None,
+            hit_pending: false,
+        }
+    }
+
+    pub fn with_address(self, address: String) -> Self {
+        DataBreakpointInfo { address, ..self }
+    }
+
+    fn update(&mut self, breakpoint: &Breakpoint) {
+        self.id = breakpoint.id.or(self.id);
+        self.verified = breakpoint.verified;
+        self.message = breakpoint.message.clone();
+    }
+}
+
+/// The line breakpoints of every source file, the function breakpoints and the data breakpoints.
+/// `setBreakpoints`, `setFunctionBreakpoints` and `setDataBreakpoints` always replace all
+/// breakpoints of their kind (or file), so they are kept in the order they were sent in.
+#[derive(Debug, Default)]
+pub struct Breakpoints {
+    files: BTreeMap<String, Vec<BreakpointInfo>>,
+    functions: Vec<FunctionBreakpointInfo>,
+    data: Vec<DataBreakpointInfo>,
+    /// file the debug adapter got an extra breakpoint for, which has to be removed once the
+    /// program stopped
+    temporary: Option<String>,
+}
+
+impl Breakpoints {
+    /// Sets `breakpoint`, replacing the one already on its line.
+    pub fn set(&mut self, breakpoint: LineBreakpoint) {
+        self.clear(&breakpoint.file, breakpoint.line);
+
+        self.files
+            .entry(breakpoint.file.clone())
+            .or_default()
+            .push(BreakpointInfo::new(breakpoint));
+    }
+
+    pub fn clear(&mut self, file: &str, line: i64) {
+        if let Some(breakpoints) = self.files.get_mut(file) {
+            breakpoints.retain(|breakpoint| !breakpoint.is_on_line(line));
+        }
+    }
+
+    pub fn toggle(&mut self, breakpoint: LineBreakpoint) {
+        match self.is_set(&breakpoint.file, breakpoint.line) {
+            true => self.clear(&breakpoint.file, breakpoint.line),
+            false => self.set(breakpoint),
+        }
+    }
+
+    /// Whether there is a breakpoint on `line`, whether the user put it there or the debug adapter
+    /// moved it there.
+    pub fn is_set(&self, file: &str, line: i64) -> bool {
+        self.files
+            .get(file)
+            .is_some_and(|breakpoints| breakpoints.iter().any(|existing| existing.is_on_line(line)))
+    }
+
+    /// Remembers that the breakpoints of `file` sent last include one the user did not set.
+    pub fn set_temporary(&mut self, file: String) {
+        self.temporary = Some(file);
+    }
+
+    /// The file whose breakpoints have to be sent again to remove a temporary one.
+    pub fn take_temporary(&mut self) -> Option<String> {
+        self.temporary.take()
+    }
+
+    /// What to send in a `setBreakpoints` request for `file`.
+    pub fn source_breakpoints(&self, file: &str) -> Vec<SourceBreakpoint> {
+        self.files
+            .get(file)
+            .into_iter()
+            .flatten()
+            .map(|breakpoint| SourceBreakpoint {
+                line: breakpoint.requested.line,
+                column: None,
+                condition: breakpoint.requested.condition.clone(),
+                hit_condition: breakpoint.requested.hit_condition.clone(),
+                log_message: breakpoint.requested.log_message.clone(),
+                mode: None,
+            })
+            .collect()
+    }
+
+    /// Records the answer to a `setBreakpoints` request for `file`, which lists the breakpoints in
+    /// the order they were sent.
+    pub fn set_verified(&mut self, file: &str, verified: &[Breakpoint]) {
+        let Some(breakpoints) = self.files.get_mut(file) else {
+            return;
+        };
+
+        if breakpoints.len() != verified.len() {
+            tracing::warn!(
+                "Sent {} breakpoints for {file} but got {} back",
+                breakpoints.len(),
+                verified.len()
+            );
+        }
+
+        for (breakpoint, verified) in breakpoints.iter_mut().zip(verified) {
+            breakpoint.update(verified);
+        }
+    }
+
+    /// Marks every breakpoint of `file` as not verified, e.g. after `setBreakpoints` failed.
+    pub fn set_unverified(&mut self, file: &str, message: &str) {
+        for breakpoint in self.files.get_mut(file).into_iter().flatten() {
+            breakpoint.verified = false;
+            breakpoint.message = Some(message.into());
+        }
+    }
+
+    /// Sets `breakpoint`, replacing the one on the same function.
+    pub fn set_function(&mut self, breakpoint: FunctionBreakpoint) {
+        self.clear_function(&breakpoint.name);
+
+        self.functions.push(FunctionBreakpointInfo {
+            requested: breakpoint,
+            id: None,
+            verified: false,
+            message: None,
+        });
+    }
+
+    pub fn clear_function(&mut self, name: &str) {
+        self.functions
+            .retain(|breakpoint| breakpoint.requested.name != name);
+    }
+
+    pub fn has_function_breakpoints(&self) -> bool {
+        !self.functions.is_empty()
+    }
+
+    /// What to send in a `setFunctionBreakpoints` request.
+    pub fn function_breakpoints(&self) -> Vec<dap_types::types::FunctionBreakpoint> {
+        self.functions
+            .iter()
+            .map(|breakpoint| dap_types::types::FunctionBreakpoint {
+                name: breakpoint.requested.name.clone(),
+                condition: breakpoint.requested.condition.clone(),
+                hit_condition: None,
+            })
+            .collect()
+    }
+
+    /// Records the answer to a `setFunctionBreakpoints` request, which lists the breakpoints in
+    /// the order they were sent.
+    pub fn set_functions_verified(&mut self, verified: &[Breakpoint]) {
+        for (breakpoint, verified) in self.functions.iter_mut().zip(verified) {
+            breakpoint.update(verified);
+        }
+    }
+
+    pub fn set_functions_unverified(&mut self, message: &str) {
+        for breakpoint in &mut self.functions {
+            breakpoint.verified = false;
+            breakpoint.message = Some(message.into());
+        }
+    }
+
+    /// Sets `breakpoint`, replacing the one on the same data.
+    pub fn set_data(&mut self, breakpoint: DataBreakpointInfo) {
+        self.clear_data(&breakpoint.data_id);
+        self.data.push(breakpoint);
+    }
+
+    pub fn clear_data(&mut self, data_id: &str) {
+        self.data.retain(|breakpoint| breakpoint.data_id != data_id);
+    }
+
+    /// What to send in a `setDataBreakpoints` request.
+    pub fn data_breakpoints(&self) -> Vec<DataBreakpoint> {
+        self.data
+            .iter()
+            .map(|breakpoint| DataBreakpoint {
+                data_id: breakpoint.data_id.clone(),
+                // adapters watch for writes by default, which is what we are after
+                access_type: None,
+                condition: None,
+                hit_condition: None,
+            })
+            .collect()
+    }
+
+    /// Records the answer to a `setDataBreakpoints` request, which lists the breakpoints in the
+    /// order they were sent.
+    pub fn set_data_verified(&mut self, verified: &[Breakpoint]) {
+        for (breakpoint, verified) in self.data.iter_mut().zip(verified) {
+            breakpoint.update(verified);
+        }
+    }
+
+    pub fn set_data_unverified(&mut self, message: &str) {
+        for breakpoint in &mut self.data {
+            breakpoint.verified = false;
+            breakpoint.message = Some(message.into());
+        }
+    }
+
+    /// Remembers which data breakpoints made the program stop, their new values are only known
+    /// once the variables were queried, see [`Breakpoints::update_data_values`].
+    pub fn handle_stop(&mut self, event: &StoppedEventBody) {
+        for breakpoint in &mut self.data {
+            breakpoint.hit = None;
+            breakpoint.hit_pending = match &event.hit_breakpoint_ids {
+                Some(ids) => breakpoint.id.is_some_and(|id| ids.contains(&id)),
+                // the adapter doesn't tell which one it was, the changed values will
+                None => event.reason == "data breakpoint",
+            };
+        }
+    }
+
+    /// Looks up the current values of all watched variables in `program_state`, recording how
+    /// they changed if the program stopped because of it.
+    pub fn update_data_values(&mut self, program_state: &ProgramState) {
+        for breakpoint in &mut self.data {
+            let Some((_, variable)) = program_state.variables().find(|(_, variable)| {
+                variable.address == breakpoint.address && variable.name == breakpoint.name
+            }) else {
+                continue;
+            };
+
+            let new_value = &variable.value;
+            if std::mem::take(&mut breakpoint.hit_pending) && *new_value != breakpoint.value {
+                breakpoint.hit = Some(DataBreakpointHit {
+                    old_value: breakpoint.value.clone(),
+                    new_value: new_value.clone(),
+                });
+            }
+
+            breakpoint.value = new_value.clone();
+        }
+    }
+
+    /// Applies a `breakpoint` event, sent when the debug adapter changes a breakpoint by itself,
+    /// e.g. verifies it once the module containing it was loaded.
+    pub fn handle_event(&mut self, event: &BreakpointEventBody) {
+        let Some(id) = event.breakpoint.id else {
+            tracing::warn!("Ignoring breakpoint event without id: {event:?}");
+            return;
+        };
+
+        if let Some(data) = self
+            .data
+            .iter_mut()
+            .find(|breakpoint| breakpoint.id == Some(id))
+        {
+            match event.reason.as_str() {
+                "removed" => self.data.retain(|breakpoint| breakpoint.id != Some(id)),
+                _ => data.update(&event.breakpoint),
+            }
+
+            return;
+        }
+
+        if let Some(function) = self
+            .functions
+            .iter_mut()
+            .find(|breakpoint| breakpoint.id == Some(id))
+        {
+            match event.reason.as_str() {
+                "removed" => self
+                    .functions
+                    .retain(|breakpoint| breakpoint.id != Some(id)),
+                _ => function.update(&event.breakpoint),
+            }
+
+            return;
+        }
+
+        let existing = self
+            .files
+            .values_mut()
+            .flatten()
+            .find(|breakpoint| breakpoint.id == Some(id));
+
+        match (event.reason.as_str(), existing) {
+            ("removed", _) => {
+                for breakpoints in self.files.values_mut() {
+                    breakpoints.retain(|breakpoint| breakpoint.id != Some(id));
+                }
+            }
+            (_, Some(breakpoint)) => breakpoint.update(&event.breakpoint),
+            ("new", None) => {
+                let file = event
+                    .breakpoint
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.path.clone());
+
+                let (Some(file), Some(line)) = (file, event.breakpoint.line) else {
+                    tracing::warn!("Ignoring new breakpoint without location: {event:?}");
+                    return;
+                };
+
+                let mut breakpoint = BreakpointInfo::new(LineBreakpoint {
+                    file: file.clone(),
+                    line,
+                    condition: None,
+                    hit_condition: None,
+                    log_message: None,
+                });
+                breakpoint.update(&event.breakpoint);
+
+                self.files.entry(file).or_default().push(breakpoint);
+            }
+            (reason, None) => {
+                tracing::debug!("Ignoring {reason} event of unknown breakpoint {id}");
+            }
+        }
+    }
+
+    /// The breakpoints to send to a new debug session. Line and function breakpoints are kept as
+    /// the user set them, data breakpoints are dropped since data ids are only valid in the
+    /// session that handed them out.
+    pub fn restarted(self) -> Self {
+        let files = self
+            .files
+            .into_iter()
+            .map(|(file, breakpoints)| {
+                let breakpoints = breakpoints
+                    .into_iter()
+                    .map(|breakpoint| BreakpointInfo::new(breakpoint.requested))
+                    .collect();
+
+                (file, breakpoints)
+            })
+            .collect();
+
+        let functions = self
+            .functions
+            .into_iter()
+            .map(|breakpoint| FunctionBreakpointInfo {
+                requested: breakpoint.requested,
+                id: None,
+                verified: false,
+                message: None,
+            })
+            .collect();
+
+        Breakpoints {
+            files,
+            functions,
+            ..Default::default()
+        }
+    }
+
+    /// Every source file with line breakpoints in it.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|(_, breakpoints)| !breakpoints.is_empty())
+            .map(|(file, _)| file.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BreakpointInfo> {
+        self.files.values().flatten()
+    }
+
+    pub fn functions(&self) -> &[FunctionBreakpointInfo] {
+        &self.functions
+    }
+
+    pub fn data(&self) -> &[DataBreakpointInfo] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dap_types::types::{Breakpoint, BreakpointEventBody, Source};
+
+    use super::{Breakpoints, DataBreakpointInfo};
+    use crate::user_request::{FunctionBreakpoint, LineBreakpoint};
+
+    const FILE: &str = "/src/Program.cs";
+
+    fn line_breakpoint(line: i64) -> LineBreakpoint {
+        LineBreakpoint {
+            file: FILE.into(),
+            line,
+            condition: None,
+            hit_condition: None,
+            log_message: None,
+        }
+    }
+
+    fn verified_breakpoint(id: i64, line: i64) -> Breakpoint {
+        Breakpoint {
+            id: Some(id),
+            verified: true,
+            message: None,
+            source: Some(Source {
+                path: Some(FILE.into()),
+                ..Default::default()
+            }),
+            line: Some(line),
+            column: None,
+            end_line: None,
+            end_column: None,
+            instruction_reference: None,
+            offset: None,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn toggling_clears_moved_breakpoint() {
+        let mut breakpoints = Breakpoints::default();
+
+        breakpoints.toggle(line_breakpoint(3));
+        breakpoints.set_verified(FILE, &[verified_breakpoint(1, 5)]);
+
+        let breakpoint = breakpoints.iter().next().unwrap();
+        assert!(breakpoint.verified);
+        assert_eq!(breakpoint.actual_line, Some(5));
+
+        // the user sees the breakpoint on the line it was moved to and toggles it there
+        breakpoints.toggle(line_breakpoint(5));
+        assert_eq!(breakpoints.iter().count(), 0);
+        assert!(breakpoints.source_breakpoints(FILE).is_empty());
+    }
+
+    #[test]
+    fn setting_replaces_breakpoint_on_same_line() {
+        let mut breakpoints = Breakpoints::default();
+
+        breakpoints.set(line_breakpoint(3));
+        breakpoints.set(LineBreakpoint {
+            condition: Some("i > 2".into()),
+            ..line_breakpoint(3)
+        });
+
+        let sent = breakpoints.source_breakpoints(FILE);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].condition.as_deref(), Some("i > 2"));
+    }
+
+    #[test]
+    fn breakpoint_events_update_known_breakpoints() {
+        let mut breakpoints = Breakpoints::default();
+
+        breakpoints.set(line_breakpoint(3));
+        breakpoints.set_verified(
+            FILE,
+            &[Breakpoint {
+                verified: false,
+                ..verified_breakpoint(1, 3)
+            }],
+        );
+
+        breakpoints.handle_event(&BreakpointEventBody {
+            reason: "changed".into(),
+            breakpoint: verified_breakpoint(1, 4),
+        });
+
+        let breakpoint = breakpoints.iter().next().unwrap();
+        assert!(breakpoint.verified);
+        assert_eq!(breakpoint.actual_line, Some(4));
+
+        breakpoints.handle_event(&BreakpointEventBody {
+            reason: "new".into(),
+            breakpoint: verified_breakpoint(2, 10),
+        });
+        assert_eq!(breakpoints.iter().count(), 2);
+
+        breakpoints.handle_event(&BreakpointEventBody {
+            reason: "removed".into(),
+            breakpoint: verified_breakpoint(1, 4),
+        });
+        assert_eq!(
+            breakpoints
+                .iter()
+                .map(|breakpoint| breakpoint.requested.line)
+                .collect::<Vec<_>>(),
+            [10]
+        );
+    }
+
+    #[test]
+    fn function_breakpoints_are_replaced_by_name() {
+        let mut breakpoints = Breakpoints::default();
+
+        breakpoints.set_function(FunctionBreakpoint {
+            name: "Program.Factorial".into(),
+            condition: None,
+        });
+        breakpoints.set_function(FunctionBreakpoint {
+            name: "Program.Factorial".into(),
+            condition: Some("n == 1".into()),
+        });
+        breakpoints.set_functions_verified(&[Breakpoint {
+            source: None,
+            line: None,
+            ..verified_breakpoint(7, 0)
+        }]);
+
+        let sent = breakpoints.function_breakpoints();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].condition.as_deref(), Some("n == 1"));
+        assert!(breakpoints.functions()[0].verified);
+
+        // function breakpoints share ids with line breakpoints
+        breakpoints.handle_event(&BreakpointEventBody {
+            reason: "removed".into(),
+            breakpoint: verified_breakpoint(7, 0),
+        });
+        assert!(!breakpoints.has_function_breakpoints());
+    }
+
+    #[test]
+    fn restart_keeps_breakpoints_of_the_user() {
+        let mut breakpoints = Breakpoints::default();
+
+        breakpoints.set(line_breakpoint(3));
+        breakpoints.set_verified(FILE, &[verified_breakpoint(1, 4)]);
+        breakpoints.set_data(DataBreakpointInfo::new(
+            "0x1234".into(),
+            "count".into(),
+            "count".into(),
+            "3".into(),
+        ));
+
+        let breakpoints = breakpoints.restarted();
+
+        let breakpoint = breakpoints.iter().next().unwrap();
+        assert_eq!(breakpoint.requested, line_breakpoint(3));
+        assert!(!breakpoint.verified);
+        assert_eq!(breakpoint.actual_line, None);
+        assert_eq!(breakpoints.files().collect::<Vec<_>>(), [FILE]);
+        assert!(breakpoints.data().is_empty());
+    }
+}
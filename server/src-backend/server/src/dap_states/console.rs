@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use dap_types::types::OutputEventBody;
+use serde::Serialize;
+
+// older lines of a chatty program are dropped, like the build output
+const MAX_CONSOLE_LINES: usize = 1000;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleCategory {
+    Stdout,
+    Stderr,
+    /// messages of the debug adapter rather than the debuggee
+    Console,
+}
+
+impl ConsoleCategory {
+    /// Adapters may use categories we don't know about, which are shown as `console` as the spec
+    /// says. Telemetry is not meant for the user and is dropped.
+    fn of(event: &OutputEventBody) -> Option<Self> {
+        match event.category.as_deref() {
+            Some("stdout") => Some(ConsoleCategory::Stdout),
+            Some("stderr") => Some(ConsoleCategory::Stderr),
+            Some("telemetry") => None,
+            _ => Some(ConsoleCategory::Console),
+        }
+    }
+}
+
+/// A line of output, e.g. printed by `Console.WriteLine`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConsoleLine {
+    pub category: ConsoleCategory,
+    pub text: String,
+    /// the stop the program was running towards when the line was printed, counting from 1, so
+    /// the line can be shown next to the state that produced it
+    pub step: usize,
+    /// whether the newline ending the line was printed yet
+    #[serde(skip)]
+    complete: bool,
+}
+
+/// The output of the debug session, split into lines.
+#[derive(Debug, Default)]
+pub struct Console {
+    lines: VecDeque<ConsoleLine>,
+}
+
+impl Console {
+    /// Adds the output of an `output` event. Output events don't necessarily end on a line break,
+    /// so the last line of each category is continued until one is printed.
+    pub fn handle_output(&mut self, event: &OutputEventBody, step: usize) {
+        let Some(category) = ConsoleCategory::of(event) else {
+            return;
+        };
+
+        for text in event.output.split_inclusive('\n') {
+            let (text, complete) = match text.strip_suffix('\n') {
+                Some(text) => (text.strip_suffix('\r').unwrap_or(text), true),
+                None => (text, false),
+            };
+
+            match self
+                .lines
+                .iter_mut()
+                .rev()
+                .find(|line| line.category == category)
+            {
+                Some(line) if !line.complete => {
+                    line.text.push_str(text);
+                    line.complete = complete;
+                }
+                _ => self.push(ConsoleLine {
+                    category,
+                    text: text.into(),
+                    step,
+                    complete,
+                }),
+            }
+        }
+    }
+
+    fn push(&mut self, line: ConsoleLine) {
+        if self.lines.len() == MAX_CONSOLE_LINES {
+            self.lines.pop_front();
+        }
+
+        self.lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &ConsoleLine> {
+        self.lines.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dap_types::types::OutputEventBody;
+
+    use super::{Console, ConsoleCategory, MAX_CONSOLE_LINES};
+
+    fn output(category: &str, output: &str) -> OutputEventBody {
+        serde_json::from_value(serde_json::json!({ "category": category, "output": output }))
+            .unwrap()
+    }
+
+    fn texts(console: &Console) -> Vec<(ConsoleCategory, &str, usize)> {
+        console
+            .lines()
+            .map(|line| (line.category, line.text.as_str(), line.step))
+            .collect()
+    }
+
+    #[test]
+    fn joins_output_into_lines_per_category() {
+        let mut console = Console::default();
+
+        console.handle_output(&output("stdout", "Hello"), 1);
+        console.handle_output(&output("stderr", "oops\r\n"), 1);
+        console.handle_output(&output("stdout", ", World\nBye\n"), 2);
+        console.handle_output(&output("telemetry", "{}\n"), 2);
+        console.handle_output(&output("important", "Loaded\n"), 2);
+
+        assert_eq!(
+            texts(&console),
+            [
+                (ConsoleCategory::Stdout, "Hello, World", 1),
+                (ConsoleCategory::Stderr, "oops", 1),
+                (ConsoleCategory::Stdout, "Bye", 2),
+                (ConsoleCategory::Console, "Loaded", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_latest_lines() {
+        let mut console = Console::default();
+
+        for line in 0..MAX_CONSOLE_LINES + 5 {
+            console.handle_output(&output("stdout", &format!("{line}\n")), 0);
+        }
+
+        assert_eq!(console.lines().count(), MAX_CONSOLE_LINES);
+        assert_eq!(console.lines().next().unwrap().text, "5");
+    }
+}
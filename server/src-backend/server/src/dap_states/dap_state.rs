@@ -4,27 +4,46 @@ use std::fmt::Debug;
 
 use crate::{
     dap_states::states::{
-        configuration_done::ConfigurationDone, initialized::Initialized, query_scopes::QueryScopes,
-        query_stack_traces::QueryStackTraces, query_threads::QueryThreads,
-        query_variables::QueryVariables, step::Step, step_in::StepIn, step_out::StepOut,
-        uninitialized::Uninitialized, wait_for_breakpoint_hit::WaitForBreakpointHit,
-        wait_for_user_input::WaitForUserInput,
+        configuration_done::ConfigurationDone, continue_::Continue, disconnect::Disconnect,
+        goto::Goto, initialized::Initialized, pause::Pause,
+        query_data_breakpoint_info::QueryDataBreakpointInfo,
+        query_exception_info::QueryExceptionInfo, query_scopes::QueryScopes,
+        query_stack_traces::QueryStackTraces, query_step_in_targets::QueryStepInTargets,
+        query_threads::QueryThreads, query_variables::QueryVariables,
+        remove_temporary_breakpoint::RemoveTemporaryBreakpoint, restart_frame::RestartFrame,
+        restart_session::RestartSession, run_to_line::RunToLine, set_breakpoints::SetBreakpoints,
+        set_data_breakpoints::SetDataBreakpoints, set_function_breakpoints::SetFunctionBreakpoints,
+        step::Step, step_in::StepIn, step_out::StepOut, uninitialized::Uninitialized,
+        wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput,
     },
-    user_request::UserRequest,
+    user_request::{LineBreakpoint, UserRequest},
 };
 
 use super::dap_state_machine::DapContext;
 
 #[enum_dispatch(DapState)]
 pub trait DapStateHandler {
+    /// Called with every successful response, along with the request it answers.
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        request: &RequestArguments,
         response: &ResponseBody,
     ) -> Option<DapState>;
 
     fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>>;
 
+    /// Called when `request` was answered with an error or abandoned after timing out. The
+    /// failure has already been recorded in the context at this point.
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        None
+    }
+
     fn handle_event(&mut self, _context: &mut DapContext, _event: &EventBody) -> Option<DapState> {
         None
     }
@@ -43,7 +62,11 @@ pub trait DapStateHandler {
         None
     }
 
-    fn handle_user_request(&mut self, _request: &UserRequest) -> Option<DapState> {
+    fn handle_user_request(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &UserRequest,
+    ) -> Option<DapState> {
         None
     }
 }
@@ -55,11 +78,25 @@ pub enum DapState {
     Initialized,
     ConfigurationDone,
     WaitForBreakpointHit,
+    QueryExceptionInfo,
     QueryThreads,
     QueryStackTraces,
+    QueryStepInTargets,
     QueryScopes,
     QueryVariables,
     WaitForUserInput,
+    Pause,
+    Continue,
+    SetBreakpoints,
+    SetFunctionBreakpoints,
+    QueryDataBreakpointInfo,
+    SetDataBreakpoints,
+    RunToLine,
+    Goto,
+    RemoveTemporaryBreakpoint,
+    RestartFrame,
+    RestartSession,
+    Disconnect,
     Step,
     StepIn,
     StepOut,
@@ -68,9 +105,60 @@ pub enum DapState {
 impl From<&UserRequest> for DapState {
     fn from(value: &UserRequest) -> Self {
         match value {
-            UserRequest::Step(thread_id) => Step(*thread_id).into(),
-            UserRequest::StepIn(thread_id) => StepIn(*thread_id).into(),
-            UserRequest::StepOut(thread_id) => StepOut(*thread_id).into(),
+            UserRequest::Step {
+                thread,
+                granularity,
+            } => Step {
+                thread: *thread,
+                granularity: *granularity,
+            }
+            .into(),
+            UserRequest::StepIn {
+                thread,
+                target,
+                granularity,
+            } => StepIn {
+                thread: *thread,
+                target: *target,
+                granularity: *granularity,
+            }
+            .into(),
+            UserRequest::StepOut {
+                thread,
+                granularity,
+            } => StepOut {
+                thread: *thread,
+                granularity: *granularity,
+            }
+            .into(),
+            UserRequest::Continue(thread_id) => Continue(*thread_id).into(),
+            UserRequest::Pause(thread_id) => Pause::thread(*thread_id).into(),
+            UserRequest::SetBreakpoint(LineBreakpoint { file, .. })
+            | UserRequest::ClearBreakpoint { file, .. }
+            | UserRequest::ToggleBreakpoint(LineBreakpoint { file, .. }) => {
+                SetBreakpoints { file: file.clone() }.into()
+            }
+            UserRequest::SetFunctionBreakpoint(..)
+            | UserRequest::ClearFunctionBreakpoint { .. } => SetFunctionBreakpoints.into(),
+            UserRequest::SetDataBreakpoint { reference, name } => QueryDataBreakpointInfo {
+                reference: *reference,
+                name: name.clone(),
+            }
+            .into(),
+            UserRequest::ClearDataBreakpoint { .. } => SetDataBreakpoints.into(),
+            UserRequest::RunToLine { file, line, thread } => RunToLine {
+                file: file.clone(),
+                line: *line,
+                thread: *thread,
+            }
+            .into(),
+            // only changes what is shown, which the state handling it does right away
+            UserRequest::SelectThread { .. } => WaitForUserInput.into(),
+            UserRequest::RestartFrame { frame_id } => RestartFrame {
+                frame_id: *frame_id,
+            }
+            .into(),
+            UserRequest::RestartSession => RestartSession.into(),
         }
     }
 }
@@ -1,17 +1,29 @@
-use std::{cell::Cell, collections::HashMap};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+};
 
-use dap_types::types::{ProtocolMessage, ProtocolMessageType, RequestArguments};
+use dap_types::types::{
+    Capabilities, ExceptionInfoResponseBody, RequestArguments, ResponseResult, StoppedEventBody,
+    ThreadEventBody,
+};
 
 use serde::Serialize;
+use serde_json::{Map, Value};
 
 use crate::{
-    dap_states::visualization_state::VisualizationState, debug_adapters::DebugAdapter,
-    user_request::UserRequest,
+    dap_client::DapMessage,
+    dap_states::visualization_state::VisualizationState,
+    debug_adapters::DebugAdapter,
+    user_request::{FunctionBreakpoint, SteppingGranularity, UserRequest},
 };
 
 use super::{
+    breakpoints::Breakpoints,
+    console::Console,
     dap_state::{DapState, DapStateHandler},
-    states::uninitialized::Uninitialized,
+    states::{disconnect::Disconnect, uninitialized::Uninitialized},
+    user_request_queue::UserRequestQueue,
 };
 
 impl VariableInfo {
@@ -80,7 +92,7 @@ impl From<&dap_types::types::Scope> for ScopeInfo {
 
 #[derive(Serialize, Clone, Debug, Default)]
 pub struct ScopeInfo {
-    #[serde(skip)]
+    /// lets the user refer to local variables, e.g. to watch them with a data breakpoint
     pub variables_reference: i64,
     pub variables: Option<Vec<VariableInfo>>,
 }
@@ -130,6 +142,35 @@ impl ProgramState {
 
         Self { threads }
     }
+
+    /// Every variable queried so far along with the scope listing it, including the members of
+    /// heap variables.
+    pub fn variables(&self) -> impl Iterator<Item = (&ScopeInfo, &VariableInfoData)> {
+        self.threads
+            .iter()
+            .filter_map(|thread| thread.stack_frames.as_ref())
+            .flatten()
+            .filter_map(|frame| frame.scopes.as_ref())
+            .flatten()
+            .flat_map(|scope| {
+                scope
+                    .variables
+                    .iter()
+                    .flatten()
+                    .map(move |variable| (scope, variable.inner()))
+            })
+    }
+
+    /// Finds the variable `name` listed in the scope or heap variable with the variables
+    /// reference `container`.
+    pub fn find_variable(&self, container: i64, name: &str) -> Option<&VariableInfoData> {
+        self.variables()
+            .find(|(scope, variable)| {
+                let variable_container = variable.parent.unwrap_or(scope.variables_reference);
+                variable_container == container && variable.name == name
+            })
+            .map(|(_, variable)| variable)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -178,19 +219,230 @@ impl VariableResolver {
     }
 }
 
+/// A call on the current line that can be stepped into.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct StepInTargetInfo {
+    /// sent along with a step in to enter this call
+    pub id: i64,
+    pub label: String,
+    pub line: Option<i64>,
+}
+
+impl From<&dap_types::types::StepInTarget> for StepInTargetInfo {
+    fn from(value: &dap_types::types::StepInTarget) -> Self {
+        StepInTargetInfo {
+            id: value.id,
+            label: value.label.clone(),
+            line: value.line,
+        }
+    }
+}
+
 #[derive(Serialize, Clone, Debug, Default)]
 pub struct ProgramState {
     pub threads: Vec<ThreadInfo>,
 }
 
+#[derive(Serialize, Clone, Debug)]
+pub struct RequestFailure {
+    pub command: String,
+    pub message: String,
+}
+
+impl RequestFailure {
+    pub fn new(request: &RequestArguments, message: impl Into<String>) -> Self {
+        let command = serde_json::to_value(request)
+            .ok()
+            .and_then(|request| Some(request.get("command")?.as_str()?.to_owned()))
+            .unwrap_or_else(|| "unknown".into());
+
+        RequestFailure {
+            command,
+            message: message.into(),
+        }
+    }
+}
+
+/// The exception the program stopped at.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ExceptionInfo {
+    /// e.g. `System.NullReferenceException`
+    pub type_name: String,
+    pub message: Option<String>,
+    pub stack_trace: Option<String>,
+}
+
+impl From<&StoppedEventBody> for ExceptionInfo {
+    /// What little the stopped event tells, for adapters that can't be asked for details.
+    fn from(value: &StoppedEventBody) -> Self {
+        ExceptionInfo {
+            type_name: value.text.clone().unwrap_or_else(|| "exception".into()),
+            message: value.description.clone(),
+            stack_trace: None,
+        }
+    }
+}
+
+impl From<&ExceptionInfoResponseBody> for ExceptionInfo {
+    fn from(value: &ExceptionInfoResponseBody) -> Self {
+        let details = value.details.as_ref();
+
+        ExceptionInfo {
+            type_name: details
+                .and_then(|details| details.full_type_name.clone().or(details.type_name.clone()))
+                .unwrap_or_else(|| value.exception_id.clone()),
+            message: details
+                .and_then(|details| details.message.clone())
+                .or(value.description.clone()),
+            stack_trace: details.and_then(|details| details.stack_trace.clone()),
+        }
+    }
+}
+
+/// What the debug adapter is asked to debug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugTarget {
+    /// start the program and stop at its entry point
+    Launch {
+        executable_path: String,
+        args: Vec<String>,
+        /// working directory of the program, the adapter decides if not set
+        cwd: Option<String>,
+        /// environment variables set in addition to the inherited ones
+        env: BTreeMap<String, String>,
+        stop_at_entry: bool,
+        /// adapter specific launch arguments, passed on as they are
+        extra_arguments: Map<String, Value>,
+    },
+    /// attach to an already running process and pause it
+    Attach { process_id: u32 },
+}
+
+impl DebugTarget {
+    /// Launches `executable_path` without arguments in the default environment.
+    pub fn launch(executable_path: impl Into<String>) -> Self {
+        DebugTarget::Launch {
+            executable_path: executable_path.into(),
+            args: Vec::new(),
+            cwd: None,
+            env: BTreeMap::new(),
+            stop_at_entry: true,
+            extra_arguments: Map::new(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DapContext {
     pub debug_adapter: DebugAdapter,
-    pub executable_path: String,
+    pub target: DebugTarget,
+    pub capabilities: Option<Capabilities>,
+    /// the thread whose stack and current line are shown, usually the one that stopped
     pub active_thread: Option<i64>,
+    /// whether the other threads stopped along with the active one, only stopped threads can be
+    /// queried
+    pub all_threads_stopped: bool,
     pub source_files: HashMap<String, String>,
     pub program_state: Option<ProgramState>,
     pub variable_resolver: VariableResolver,
+    /// requests that failed or timed out since the program last stopped
+    pub request_failures: Vec<RequestFailure>,
+    pub breakpoints: Breakpoints,
+    /// whether the debuggee is executing, as opposed to being stopped or not started yet
+    pub running: bool,
+    /// set if the program is stopped because of an exception
+    pub exception: Option<ExceptionInfo>,
+    /// calls on the current line of the active thread that can be stepped into
+    pub step_in_targets: Vec<StepInTargetInfo>,
+    /// set once the debuggee exited, adapters don't necessarily report it before terminating
+    pub exit_code: Option<i64>,
+    /// whether the debug session is over, nothing can be asked of the debug adapter anymore
+    pub terminated: bool,
+    /// how many times the program stopped so far
+    pub step: usize,
+    pub console: Console,
+}
+
+impl DapContext {
+    fn new(debug_adapter: DebugAdapter, target: DebugTarget) -> Self {
+        DapContext {
+            debug_adapter,
+            target,
+            capabilities: None,
+            active_thread: None,
+            all_threads_stopped: true,
+            source_files: HashMap::new(),
+            program_state: None,
+            variable_resolver: VariableResolver::new(),
+            request_failures: Vec::new(),
+            breakpoints: Breakpoints::default(),
+            running: false,
+            exception: None,
+            step_in_targets: Vec::new(),
+            exit_code: None,
+            terminated: false,
+            step: 0,
+            console: Console::default(),
+        }
+    }
+
+    /// Whether the debug adapter advertised the given capability.
+    pub fn supports(&self, capability: impl FnOnce(&Capabilities) -> Option<bool>) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(capability)
+            .unwrap_or(false)
+    }
+
+    /// Keeps the known threads up to date while the program runs, so threads started in the
+    /// meantime are shown right away. Their stack is only known once the program stopped again.
+    fn handle_thread_event(&mut self, event: &ThreadEventBody) {
+        let program_state = self.program_state.get_or_insert_default();
+
+        match event.reason.as_str() {
+            "started" => {
+                if !program_state
+                    .threads
+                    .iter()
+                    .any(|thread| thread.id == event.thread_id)
+                {
+                    program_state.threads.push(ThreadInfo {
+                        id: event.thread_id,
+                        // the name is only known after asking for all threads
+                        name: format!("Thread {}", event.thread_id),
+                        stack_frames: Some(Vec::new()),
+                    });
+                }
+            }
+            "exited" => {
+                program_state
+                    .threads
+                    .retain(|thread| thread.id != event.thread_id);
+
+                if self.active_thread == Some(event.thread_id) {
+                    self.active_thread = None;
+                }
+            }
+            reason => tracing::debug!("Ignoring thread event {reason:?}"),
+        }
+    }
+
+    /// What to send as the granularity of a step, if the debug adapter supports it at all.
+    pub fn stepping_granularity(
+        &self,
+        granularity: Option<SteppingGranularity>,
+    ) -> Option<dap_types::types::SteppingGranularity> {
+        let granularity = granularity?;
+
+        if !self.supports(|capabilities| capabilities.supports_stepping_granularity) {
+            tracing::warn!(
+                "Debug adapter does not support stepping granularity, ignoring {granularity:?}"
+            );
+            return None;
+        }
+
+        Some(granularity.into())
+    }
 }
 
 #[derive(Debug)]
@@ -198,96 +450,240 @@ pub struct DapStateMachine {
     state: DapState,
     context: DapContext,
     might_have_new_requests: Cell<bool>,
-    program_terminated: bool,
+    /// the user asked to start over once the current session is over
+    restart_requested: bool,
+    /// user requests waiting for the program to wait for user input again
+    user_requests: UserRequestQueue,
 }
 
 impl DapStateMachine {
-    pub fn new(debug_adapter: DebugAdapter, executable_path: String) -> Self {
+    pub fn new(debug_adapter: DebugAdapter, target: DebugTarget) -> Self {
         DapStateMachine {
             state: Uninitialized.into(),
-            context: DapContext {
-                debug_adapter,
-                executable_path,
-                active_thread: None,
-                source_files: HashMap::new(),
-                program_state: None,
-                variable_resolver: VariableResolver::new(),
-            },
+            context: DapContext::new(debug_adapter, target),
             might_have_new_requests: true.into(),
-            program_terminated: false,
+            restart_requested: false,
+            user_requests: UserRequestQueue::default(),
+        }
+    }
+
+    /// A state machine for a new session debugging the same target, to be connected to a new
+    /// debug adapter. Only the breakpoints of the user are kept.
+    pub fn restarted(self) -> Self {
+        let mut state_machine =
+            DapStateMachine::new(self.context.debug_adapter, self.context.target);
+        state_machine.context.breakpoints = self.context.breakpoints.restarted();
+
+        state_machine
+    }
+
+    /// Sets function breakpoints, sent to the debug adapter before the configuration is done.
+    pub fn with_function_breakpoints(
+        mut self,
+        breakpoints: impl IntoIterator<Item = FunctionBreakpoint>,
+    ) -> Self {
+        for breakpoint in breakpoints {
+            self.context.breakpoints.set_function(breakpoint);
         }
+
+        self
     }
 
     pub fn build_visualization_state(&self) -> VisualizationState {
-        VisualizationState::from(&self.context)
+        VisualizationState::from(&self.context).with_queued_user_requests(self.user_requests.len())
     }
 
     pub fn program_terminated(&self) -> bool {
-        self.program_terminated
+        self.context.terminated
+    }
+
+    pub fn restart_requested(&self) -> bool {
+        self.restart_requested
+    }
+
+    /// Ends the session for good, whatever the program is doing, e.g. because dapviz is shutting
+    /// down. Queued user requests and a requested restart are dropped.
+    pub fn end_session(mut self) -> Self {
+        self.user_requests.clear();
+        self.restart_requested = false;
+        self.transition(Disconnect.into());
+
+        self
     }
 
     fn transition(&mut self, state: DapState) {
         tracing::debug!("Transition into '{:?}'", state);
+
+        match &state {
+            DapState::WaitForBreakpointHit(..) => self.context.running = true,
+            // all variables are known now, so changes of watched ones can be shown
+            DapState::WaitForUserInput(..) => {
+                if let Some(program_state) = &self.context.program_state {
+                    self.context.breakpoints.update_data_values(program_state);
+                }
+            }
+            _ => (),
+        }
+
         self.state = state;
         self.might_have_new_requests.set(true);
     }
 
-    pub fn process_dap_messages(mut self, messages: &[ProtocolMessage]) -> Self {
-        for message in messages {
-            let next_state = match &message.type_ {
-                ProtocolMessageType::Request(request_arguments) => {
-                    tracing::debug!("Received reverse request: {:?}", request_arguments);
-                    self.state
-                        .handle_reverse_request(&mut self.context, request_arguments)
+    pub fn process_dap_message(mut self, message: &DapMessage) -> Self {
+        let next_state = match message {
+            DapMessage::ReverseRequest(request_arguments) => {
+                tracing::debug!("Received reverse request: {:?}", request_arguments);
+                self.state
+                    .handle_reverse_request(&mut self.context, request_arguments)
+            }
+            DapMessage::Response { request, response } => {
+                tracing::debug!("Received response: {:?}", response);
+
+                // the debug adapter is done with us after this, whether it succeeded or not
+                if let RequestArguments::disconnect(..) = request {
+                    self.context.terminated = true;
                 }
-                ProtocolMessageType::Response(response) if response.success => {
-                    tracing::debug!("Received response: {:?}", response);
 
-                    match &response.result {
-                        dap_types::types::ResponseResult::Success { body } => {
-                            self.state.handle_response(&mut self.context, body)
-                        }
-                        dap_types::types::ResponseResult::Error {
-                            command,
-                            message,
-                            show_user: _,
-                        } => {
-                            tracing::error!("Request \"{command}\" failed: {message}");
-                            None
-                        }
+                match &response.result {
+                    ResponseResult::Success { body } if response.success => {
+                        self.state.handle_response(&mut self.context, request, body)
+                    }
+                    ResponseResult::Error { message, .. } => {
+                        return self.process_request_failure(request, message);
+                    }
+                    ResponseResult::Success { .. } => {
+                        return self.process_request_failure(request, "request was not successful");
                     }
                 }
-                ProtocolMessageType::Response(response) => {
-                    tracing::error!("Received error response: {:?}", response);
-                    None
-                }
-                ProtocolMessageType::Event(event_body) => {
-                    tracing::debug!("Received event: {:?}", event_body);
-
-                    if matches!(event_body, dap_types::types::EventBody::terminated(_)) {
-                        self.program_terminated = true;
+            }
+            DapMessage::Event(event_body) => {
+                tracing::debug!("Received event: {:?}", event_body);
+
+                // these matter no matter what we are doing at the moment
+                match event_body {
+                    dap_types::types::EventBody::terminated(_) => {
+                        self.context.terminated = true;
+                        self.context.running = false;
                     }
-
-                    self.state.handle_event(&mut self.context, event_body)
+                    dap_types::types::EventBody::stopped(event) => {
+                        self.context.running = false;
+                        self.context.step += 1;
+                        if event.thread_id.is_some() {
+                            self.context.active_thread = event.thread_id;
+                        }
+                        self.context.all_threads_stopped =
+                            event.all_threads_stopped.unwrap_or(false);
+                        self.context.exception = None;
+                        self.context.step_in_targets.clear();
+                        self.context.breakpoints.handle_stop(event);
+                    }
+                    dap_types::types::EventBody::exited(event) => {
+                        self.context.exit_code = Some(event.exit_code);
+                        self.context.running = false;
+                    }
+                    dap_types::types::EventBody::continued(_) => self.context.running = true,
+                    dap_types::types::EventBody::breakpoint(event) => {
+                        self.context.breakpoints.handle_event(event)
+                    }
+                    dap_types::types::EventBody::thread(event) => {
+                        self.context.handle_thread_event(event)
+                    }
+                    dap_types::types::EventBody::output(event) => {
+                        // shown along with the stop the program was running towards
+                        self.context
+                            .console
+                            .handle_output(event, self.context.step + 1)
+                    }
+                    _ => (),
                 }
-            };
 
-            if let Some(next_state) = next_state {
-                self.transition(next_state);
+                self.state.handle_event(&mut self.context, event_body)
             }
+        };
+
+        if let Some(next_state) = next_state {
+            self.transition(next_state);
         }
 
         self
     }
 
-    pub fn process_user_request(mut self, request: &UserRequest) -> Self {
-        if let Some(next_state) = self.state.handle_user_request(request) {
+    /// Lets the current state know that `request` failed or was abandoned, e.g. after timing out.
+    pub fn process_request_failure(mut self, request: &RequestArguments, message: &str) -> Self {
+        let failure = RequestFailure::new(request, message);
+        tracing::error!(
+            "Request \"{}\" failed: {}",
+            failure.command,
+            failure.message
+        );
+        self.context.request_failures.push(failure);
+
+        // there is no point in waiting for a session that failed to end, dropping the connection
+        // ends it as well
+        if let RequestArguments::terminate(..) | RequestArguments::disconnect(..) = request {
+            self.context.terminated = true;
+        }
+
+        if let Some(next_state) =
+            self.state
+                .handle_request_failure(&mut self.context, request, message)
+        {
             self.transition(next_state);
         }
 
         self
     }
 
+    pub fn supports_cancel_request(&self) -> bool {
+        self.context
+            .supports(|capabilities| capabilities.supports_cancel_request)
+    }
+
+    pub fn process_user_request(mut self, request: &UserRequest) -> Self {
+        match request {
+            // a runaway program has to be restartable as well, so don't leave this to the state
+            UserRequest::RestartSession => {
+                self.user_requests.clear();
+                self.restart_requested = true;
+                self.transition(request.into());
+            }
+            // only makes sense right now, while the program is running
+            UserRequest::Pause(..) => self.apply_user_request(request),
+            _ if self.waits_for_user_input() && self.user_requests.is_empty() => {
+                self.apply_user_request(request)
+            }
+            _ => self.user_requests.push(request.clone()),
+        }
+
+        self
+    }
+
+    /// Whether a queued user request can be applied now. Requests are applied one at a time, so
+    /// every stop they lead to gets shown.
+    pub fn has_applicable_user_request(&self) -> bool {
+        self.waits_for_user_input() && !self.user_requests.is_empty()
+    }
+
+    pub fn process_queued_user_request(mut self) -> Self {
+        if self.waits_for_user_input()
+            && let Some(request) = self.user_requests.pop()
+        {
+            self.apply_user_request(&request);
+        }
+
+        self
+    }
+
+    fn waits_for_user_input(&self) -> bool {
+        matches!(self.state, DapState::WaitForUserInput(..))
+    }
+
+    fn apply_user_request(&mut self, request: &UserRequest) {
+        if let Some(next_state) = self.state.handle_user_request(&mut self.context, request) {
+            self.transition(next_state);
+        }
+    }
+
     pub fn next_dap_requests(&self) -> Option<Box<[RequestArguments]>> {
         if !self.might_have_new_requests.replace(false) {
             return None;
@@ -296,3 +692,189 @@ impl DapStateMachine {
         self.state.next_requests(&self.context)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use dap_types::types::RequestArguments;
+    use serde_json::{Value, json};
+
+    use super::{DapStateMachine, DebugTarget, ProgramState, ThreadInfo};
+    use crate::{
+        dap_client::DapMessage,
+        dap_states::states::{
+            query_stack_traces::QueryStackTraces, query_variables::MAX_VARIABLE_REQUESTS,
+        },
+        debug_adapters::DebugAdapter,
+    };
+
+    /// A state machine that just learned which threads exist after the program stopped.
+    fn stopped_with_threads(threads: &[i64]) -> DapStateMachine {
+        let mut state_machine = DapStateMachine::new(
+            DebugAdapter::NetCoreDbg,
+            DebugTarget::launch("/src/bin/Program.dll"),
+        );
+
+        state_machine.context.active_thread = threads.first().copied();
+        state_machine.context.program_state = Some(ProgramState {
+            threads: threads
+                .iter()
+                .map(|&id| ThreadInfo {
+                    id,
+                    name: format!("Thread {id}"),
+                    stack_frames: None,
+                })
+                .collect(),
+        });
+        state_machine.transition(QueryStackTraces.into());
+
+        state_machine
+    }
+
+    fn respond(request: RequestArguments, body: Value) -> DapMessage {
+        let command = serde_json::to_value(&request).unwrap()["command"].clone();
+        let response = serde_json::from_value(json!({
+            "seq": 0,
+            "type": "response",
+            "request_seq": 0,
+            "success": true,
+            "command": command,
+            "body": body,
+        }))
+        .unwrap();
+
+        DapMessage::Response { request, response }
+    }
+
+    /// Answers the requests of the state machine, always the most recently sent one first, until
+    /// it has nothing left to ask. Returns every request that was sent.
+    fn answer_latest_first(
+        mut state_machine: DapStateMachine,
+        answer: impl Fn(&RequestArguments) -> Value,
+    ) -> (DapStateMachine, Vec<RequestArguments>) {
+        let mut sent = Vec::new();
+        let mut in_flight = Vec::new();
+
+        loop {
+            if let Some(requests) = state_machine.next_dap_requests() {
+                sent.extend(requests.iter().cloned());
+                in_flight.extend(requests);
+            }
+
+            let Some(request) = in_flight.pop() else {
+                break;
+            };
+
+            let body = answer(&request);
+            state_machine = state_machine.process_dap_message(&respond(request, body));
+        }
+
+        (state_machine, sent)
+    }
+
+    fn variable(name: &str, value: &str, reference: i64, memory_reference: &str) -> Value {
+        json!({
+            "name": name,
+            "value": value,
+            "type": "int",
+            "variablesReference": reference,
+            "memoryReference": memory_reference,
+        })
+    }
+
+    fn variables_requests(sent: &[RequestArguments]) -> Vec<i64> {
+        sent.iter()
+            .filter_map(|request| match request {
+                RequestArguments::variables(arguments) => Some(arguments.variables_reference),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assembles_program_state_from_responses_out_of_order() {
+        let frame = |id: i64, name: &str| {
+            json!({
+                "id": id,
+                "name": name,
+                "line": id,
+                "column": 1,
+                "source": { "path": "/src/Program.cs" },
+            })
+        };
+
+        let (state_machine, sent) =
+            answer_latest_first(stopped_with_threads(&[1, 2]), |request| match request {
+                RequestArguments::stackTrace(arguments) => match arguments.thread_id {
+                    1 => json!({ "stackFrames": [frame(10, "Main"), frame(11, "Run")] }),
+                    _ => json!({ "stackFrames": [frame(20, "Work")] }),
+                },
+                RequestArguments::scopes(arguments) => json!({ "scopes": [{
+                    "name": "Locals",
+                    "variablesReference": arguments.frame_id * 10,
+                    "expensive": false,
+                }] }),
+                RequestArguments::variables(arguments) => match arguments.variables_reference {
+                    100 => json!({ "variables": [
+                        variable("numbers", "List<int>", 1000, "0x1000"),
+                        variable("count", "2", 0, "0x1"),
+                    ] }),
+                    1000 => json!({ "variables": [variable("[0]", "7", 0, "0x1008")] }),
+                    _ => json!({ "variables": [] }),
+                },
+                request => panic!("Unexpected request {request:?}"),
+            });
+
+        assert!(state_machine.waits_for_user_input());
+
+        let program_state = state_machine.context.program_state.as_ref().unwrap();
+        let frames: Vec<_> = program_state
+            .threads
+            .iter()
+            .map(|thread| {
+                let frames = thread.stack_frames.as_ref().unwrap();
+                assert!(frames.iter().all(|frame| frame.scopes.is_some()));
+
+                (thread.id, frames.iter().map(|frame| frame.id).collect())
+            })
+            .collect();
+        assert_eq!(frames, [(1, vec![10, 11]), (2, vec![20])]);
+
+        assert_eq!(
+            program_state.find_variable(100, "count").unwrap().value,
+            "2"
+        );
+        assert_eq!(program_state.find_variable(1000, "[0]").unwrap().value, "7");
+
+        let mut variables_requests = variables_requests(&sent);
+        variables_requests.sort();
+        assert_eq!(variables_requests, [100, 110, 200, 1000]);
+    }
+
+    #[test]
+    fn stops_querying_variables_at_request_limit() {
+        let frame = json!({ "id": 1, "name": "Main", "line": 1, "column": 1 });
+
+        // every variable has two members of its own, so there is always something left to query
+        let (state_machine, sent) =
+            answer_latest_first(stopped_with_threads(&[1]), |request| match request {
+                RequestArguments::stackTrace(_) => json!({ "stackFrames": [frame] }),
+                RequestArguments::scopes(_) => json!({ "scopes": [{
+                    "name": "Locals",
+                    "variablesReference": 1,
+                    "expensive": false,
+                }] }),
+                RequestArguments::variables(arguments) => {
+                    let reference = arguments.variables_reference;
+                    let members = [reference * 2, reference * 2 + 1].map(|member| {
+                        variable("member", "{...}", member, &format!("0x{member:x}"))
+                    });
+
+                    json!({ "variables": members })
+                }
+                request => panic!("Unexpected request {request:?}"),
+            });
+
+        assert!(state_machine.waits_for_user_input());
+        assert_eq!(variables_requests(&sent).len(), MAX_VARIABLE_REQUESTS);
+    }
+}
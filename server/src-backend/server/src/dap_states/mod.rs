@@ -1,5 +1,8 @@
 pub mod states;
 
+pub mod breakpoints;
+pub mod console;
 pub mod dap_state;
 pub mod dap_state_machine;
+pub mod user_request_queue;
 pub mod visualization_state;
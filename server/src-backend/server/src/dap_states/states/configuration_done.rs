@@ -1,34 +1,139 @@
-use dap_types::types::{RequestArguments, ResponseBody};
+use dap_types::types::{RequestArguments, ResponseBody, SetExceptionBreakpointsArguments};
 
 use crate::dap_states::{
     dap_state::{DapState, DapStateHandler},
-    dap_state_machine::DapContext,
+    dap_state_machine::{DapContext, DebugTarget},
 };
 
-use super::wait_for_breakpoint_hit::WaitForBreakpointHit;
+use super::{
+    disconnect::Disconnect, pause::Pause, set_breakpoints::SetBreakpoints,
+    set_function_breakpoints::SetFunctionBreakpoints,
+    wait_for_breakpoint_hit::WaitForBreakpointHit,
+};
 
 #[derive(Debug)]
 pub struct ConfigurationDone;
 
+impl ConfigurationDone {
+    /// The exception filters to enable: the ones the adapter enables by default, or all of them if
+    /// it has no defaults, e.g. `all` (thrown) and `user-unhandled` of netcoredbg.
+    fn exception_filters(context: &DapContext) -> Vec<String> {
+        let filters = context
+            .capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.exception_breakpoint_filters.as_deref())
+            .unwrap_or_default();
+
+        let defaults: Vec<_> = filters
+            .iter()
+            .filter(|filter| filter.default == Some(true))
+            .map(|filter| filter.filter.clone())
+            .collect();
+
+        match defaults.is_empty() {
+            true => filters.iter().map(|filter| filter.filter.clone()).collect(),
+            false => defaults,
+        }
+    }
+}
+
 impl DapStateHandler for ConfigurationDone {
     fn next_requests(
         &self,
-        _context: &DapContext,
+        context: &DapContext,
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
-        Some(Box::new([RequestArguments::configurationDone(None)]))
+        let exception_filters = ConfigurationDone::exception_filters(context);
+
+        let mut requests = Vec::new();
+
+        if !exception_filters.is_empty() {
+            requests.push(RequestArguments::setExceptionBreakpoints(
+                SetExceptionBreakpointsArguments {
+                    filters: exception_filters,
+                    filter_options: None,
+                    exception_options: None,
+                },
+            ));
+        }
+
+        // breakpoints given on the command line or kept when restarting have to be in place before
+        // the program starts
+        for file in context.breakpoints.files() {
+            requests.push(SetBreakpoints::request(context, file, []));
+        }
+
+        if context.breakpoints.has_function_breakpoints() {
+            requests.push(SetFunctionBreakpoints::request(context));
+        }
+
+        requests.push(RequestArguments::configurationDone(None));
+
+        Some(requests.into())
     }
 
     fn handle_response(
         &mut self,
-        _context: &mut DapContext,
+        context: &mut DapContext,
+        request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
-            ResponseBody::configurationDone(..) => Some(WaitForBreakpointHit.into()),
+            // configurationDone was sent right after it, wait for that
+            ResponseBody::setExceptionBreakpoints(..) => None,
+            ResponseBody::setBreakpoints(body) => {
+                if let Some(file) = breakpoints_file(request) {
+                    context.breakpoints.set_verified(file, &body.breakpoints);
+                }
+
+                None
+            }
+            ResponseBody::setFunctionBreakpoints(body) => {
+                context
+                    .breakpoints
+                    .set_functions_verified(&body.breakpoints);
+
+                None
+            }
+            ResponseBody::configurationDone(..) => match context.target {
+                // launched programs stop at their entry point by themselves
+                DebugTarget::Launch { .. } => Some(WaitForBreakpointHit.into()),
+                DebugTarget::Attach { .. } => Some(Pause::any_thread().into()),
+            },
             _ => {
                 tracing::error!("Unexpected response: {:?}", response);
                 None
             }
         }
     }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        message: &str,
+    ) -> Option<DapState> {
+        // the program never starts without it, end the session so the failure gets shown
+        if let RequestArguments::configurationDone(..) = request {
+            return Some(Disconnect.into());
+        }
+
+        // the program still runs, just without the breakpoints
+        if let RequestArguments::setFunctionBreakpoints(..) = request {
+            context.breakpoints.set_functions_unverified(message);
+        }
+
+        if let Some(file) = breakpoints_file(request) {
+            context.breakpoints.set_unverified(file, message);
+        }
+
+        None
+    }
+}
+
+/// The source file a `setBreakpoints` request was sent for.
+fn breakpoints_file(request: &RequestArguments) -> Option<&str> {
+    match request {
+        RequestArguments::setBreakpoints(arguments) => arguments.source.path.as_deref(),
+        _ => None,
+    }
 }
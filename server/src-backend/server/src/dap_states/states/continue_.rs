@@ -0,0 +1,52 @@
+use dap_types::types::{ContinueArguments, RequestArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::{wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput};
+
+/// Lets the debuggee run until it hits a breakpoint or gets paused.
+#[derive(Debug)]
+pub struct Continue(pub i64);
+
+impl DapStateHandler for Continue {
+    fn next_requests(
+        &self,
+        _context: &DapContext,
+    ) -> Option<Box<[dap_types::types::RequestArguments]>> {
+        Some(Box::new([RequestArguments::continue_(ContinueArguments {
+            thread_id: self.0,
+            single_thread: false.into(),
+        })]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &dap_types::types::ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            dap_types::types::ResponseBody::continue_(..) => {
+                context.active_thread = Some(self.0);
+                Some(WaitForBreakpointHit.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program did not move, so the current visualization is still valid
+        Some(WaitForUserInput.into())
+    }
+}
@@ -0,0 +1,39 @@
+use dap_types::types::{DisconnectArguments, RequestArguments, ResponseBody};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, DebugTarget},
+};
+
+/// Ends the session for good because dapviz is shutting down. A launched debuggee is ended with
+/// it, so it isn't left running once the debug adapter gets killed, while an attached one is
+/// left alone. The session is over once the debug adapter answered `disconnect` or the request
+/// timed out.
+#[derive(Debug)]
+pub struct Disconnect;
+
+impl DapStateHandler for Disconnect {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        let terminate_debuggee = matches!(context.target, DebugTarget::Launch { .. });
+
+        Some(Box::new([RequestArguments::disconnect(Some(
+            DisconnectArguments {
+                restart: Some(false),
+                terminate_debuggee: Some(terminate_debuggee),
+                suspend_debuggee: None,
+            },
+        ))]))
+    }
+
+    fn handle_response(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        // answers to requests of the state we came from don't matter anymore
+        tracing::debug!("Ignoring response while disconnecting: {:?}", response);
+
+        None
+    }
+}
@@ -0,0 +1,52 @@
+use dap_types::types::{GotoArguments, RequestArguments, ResponseBody};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::{wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput};
+
+/// Moves `thread` to a target found by `gotoTargets`. The debug adapter reports the new location
+/// with a `stopped` event.
+#[derive(Debug)]
+pub struct Goto {
+    pub thread: i64,
+    pub target_id: i64,
+}
+
+impl DapStateHandler for Goto {
+    fn next_requests(&self, _context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([RequestArguments::goto(GotoArguments {
+            thread_id: self.thread,
+            target_id: self.target_id,
+        })]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::goto => {
+                context.active_thread = Some(self.thread);
+                Some(WaitForBreakpointHit.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        Some(WaitForUserInput.into())
+    }
+}
@@ -4,7 +4,7 @@ use serde_json::{Map, Value};
 use crate::{
     dap_states::{
         dap_state::{DapState, DapStateHandler},
-        dap_state_machine::DapContext,
+        dap_state_machine::{DapContext, DebugTarget},
     },
     debug_adapters::DebugAdapter,
 };
@@ -19,32 +19,65 @@ impl DapStateHandler for Initialized {
         &self,
         context: &DapContext,
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
-        let arguments = Map::from_iter(match context.debug_adapter {
-            DebugAdapter::NetCoreDbg => [
-                // arguments taken from
-                // https://github.com/Samsung/netcoredbg/blob/83214c3993c052a0ccb8854b913e028c5e365bc6/src/protocols/vscodeprotocol.cpp#L585
-                ("cwd".to_owned(), "".into()),
-                ("env".to_owned(), Value::Object(Map::from_iter([]))),
-                ("justMyCode".to_owned(), true.into()),
-                ("enableStepFiltering".to_owned(), true.into()),
-                ("stopAtEntry".to_owned(), true.into()),
-                ("program".to_owned(), context.executable_path.clone().into()),
-                ("args".to_owned(), Value::Array(vec![])),
-            ],
-        });
-
-        Some(Box::new([RequestArguments::launch(
-            dap_types::types::Either::Second(serde_json::Value::Object(arguments)),
-        )]))
+        let request = match &context.target {
+            DebugTarget::Launch {
+                executable_path,
+                args,
+                cwd,
+                env,
+                stop_at_entry,
+                extra_arguments,
+            } => {
+                let mut arguments = Map::from_iter(match context.debug_adapter {
+                    DebugAdapter::NetCoreDbg => [
+                        // arguments taken from
+                        // https://github.com/Samsung/netcoredbg/blob/83214c3993c052a0ccb8854b913e028c5e365bc6/src/protocols/vscodeprotocol.cpp#L585
+                        ("cwd".to_owned(), cwd.clone().unwrap_or_default().into()),
+                        (
+                            "env".to_owned(),
+                            Value::Object(Map::from_iter(
+                                env.iter()
+                                    .map(|(key, value)| (key.clone(), value.clone().into())),
+                            )),
+                        ),
+                        ("justMyCode".to_owned(), true.into()),
+                        ("enableStepFiltering".to_owned(), true.into()),
+                        ("stopAtEntry".to_owned(), (*stop_at_entry).into()),
+                        ("program".to_owned(), executable_path.clone().into()),
+                        ("args".to_owned(), args.clone().into()),
+                    ],
+                });
+
+                // e.g. fields of a launch.json configuration, these win over our defaults
+                arguments.extend(extra_arguments.clone());
+
+                RequestArguments::launch(dap_types::types::Either::Second(Value::Object(arguments)))
+            }
+            DebugTarget::Attach { process_id } => {
+                let arguments = Map::from_iter(match context.debug_adapter {
+                    DebugAdapter::NetCoreDbg => [
+                        ("processId".to_owned(), (*process_id).into()),
+                        ("justMyCode".to_owned(), true.into()),
+                        ("enableStepFiltering".to_owned(), true.into()),
+                    ],
+                });
+
+                RequestArguments::attach(dap_types::types::Either::Second(Value::Object(arguments)))
+            }
+        };
+
+        Some(Box::new([request]))
     }
 
     fn handle_response(
         &mut self,
         _context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
-            dap_types::types::ResponseBody::launch(..) => Some(ConfigurationDone.into()),
+            dap_types::types::ResponseBody::launch(..)
+            | dap_types::types::ResponseBody::attach(..) => Some(ConfigurationDone.into()),
             _ => {
                 tracing::error!("Unexpected response: {:?}", response);
                 None
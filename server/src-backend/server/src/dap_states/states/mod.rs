@@ -1,9 +1,23 @@
 pub mod configuration_done;
+pub mod continue_;
+pub mod disconnect;
+pub mod goto;
 pub mod initialized;
+pub mod pause;
+pub mod query_data_breakpoint_info;
+pub mod query_exception_info;
 pub mod query_scopes;
 pub mod query_stack_traces;
+pub mod query_step_in_targets;
 pub mod query_threads;
 pub mod query_variables;
+pub mod remove_temporary_breakpoint;
+pub mod restart_frame;
+pub mod restart_session;
+pub mod run_to_line;
+pub mod set_breakpoints;
+pub mod set_data_breakpoints;
+pub mod set_function_breakpoints;
 pub mod step;
 pub mod step_in;
 pub mod step_out;
@@ -0,0 +1,85 @@
+use dap_types::types::{PauseArguments, RequestArguments, ResponseBody};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::{query_threads::QueryThreads, wait_for_breakpoint_hit::WaitForBreakpointHit};
+
+/// Suspends the running debuggee, looking up a thread to pause first if none is given.
+#[derive(Debug)]
+pub struct Pause {
+    thread_id: Option<i64>,
+}
+
+impl Pause {
+    pub fn any_thread() -> Self {
+        Pause { thread_id: None }
+    }
+
+    pub fn thread(thread_id: i64) -> Self {
+        Pause {
+            thread_id: Some(thread_id),
+        }
+    }
+}
+
+impl DapStateHandler for Pause {
+    fn next_requests(
+        &self,
+        _context: &DapContext,
+    ) -> Option<Box<[dap_types::types::RequestArguments]>> {
+        Some(Box::new([match self.thread_id {
+            Some(thread_id) => RequestArguments::pause(PauseArguments { thread_id }),
+            None => RequestArguments::threads(None),
+        }]))
+    }
+
+    fn handle_response(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &dap_types::types::ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::threads(threads) => match threads.threads.first() {
+                Some(thread) => Some(Pause::thread(thread.id).into()),
+                None => {
+                    tracing::warn!("Debuggee has no threads to pause");
+                    Some(WaitForBreakpointHit.into())
+                }
+            },
+            // the stopped event follows the response
+            ResponseBody::pause(..) => Some(WaitForBreakpointHit.into()),
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the debuggee keeps running, it can still stop at a breakpoint
+        Some(WaitForBreakpointHit.into())
+    }
+
+    fn handle_event(
+        &mut self,
+        context: &mut DapContext,
+        event: &dap_types::types::EventBody,
+    ) -> Option<DapState> {
+        match event {
+            // e.g. adapters that stop the debuggee when attaching
+            dap_types::types::EventBody::stopped(stopped) => {
+                Some(QueryThreads::after_stop(context, stopped))
+            }
+            _ => None,
+        }
+    }
+}
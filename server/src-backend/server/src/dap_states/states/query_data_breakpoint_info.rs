@@ -0,0 +1,89 @@
+use dap_types::types::{DataBreakpointInfoArguments, RequestArguments, ResponseBody};
+
+use crate::dap_states::{
+    breakpoints::DataBreakpointInfo,
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, RequestFailure},
+};
+
+use super::{set_data_breakpoints::SetDataBreakpoints, wait_for_user_input::WaitForUserInput};
+
+/// Asks the debug adapter whether the variable `name` of the container `reference` can be
+/// watched, setting a data breakpoint on it if so.
+#[derive(Debug)]
+pub struct QueryDataBreakpointInfo {
+    pub reference: i64,
+    pub name: String,
+}
+
+impl DapStateHandler for QueryDataBreakpointInfo {
+    fn next_requests(&self, _context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([RequestArguments::dataBreakpointInfo(
+            DataBreakpointInfoArguments {
+                variables_reference: Some(self.reference),
+                name: self.name.clone(),
+                frame_id: None,
+                bytes: None,
+                as_address: None,
+                mode: None,
+            },
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        let ResponseBody::dataBreakpointInfo(body) = response else {
+            tracing::error!("Unexpected response: {:?}", response);
+            return None;
+        };
+
+        // the description tells why the variable can't be watched
+        let Some(data_id) = body.data_id.clone() else {
+            let failure = RequestFailure::new(request, body.description.clone());
+            tracing::warn!("Cannot watch {}: {}", self.name, failure.message);
+            context.request_failures.push(failure);
+
+            return Some(WaitForUserInput.into());
+        };
+
+        let variable = context
+            .program_state
+            .as_ref()
+            .and_then(|program_state| program_state.find_variable(self.reference, &self.name));
+
+        let mut breakpoint = DataBreakpointInfo::new(
+            data_id,
+            body.description.clone(),
+            self.name.clone(),
+            variable
+                .map(|variable| variable.value.clone())
+                .unwrap_or_default(),
+        );
+
+        match variable {
+            Some(variable) => breakpoint = breakpoint.with_address(variable.address.clone()),
+            None => tracing::warn!(
+                "Watched variable {} of {} is not known, its changes can't be shown",
+                self.name,
+                self.reference
+            ),
+        }
+
+        context.breakpoints.set_data(breakpoint);
+
+        Some(SetDataBreakpoints.into())
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        Some(WaitForUserInput.into())
+    }
+}
@@ -0,0 +1,56 @@
+use dap_types::types::{ExceptionInfoArguments, RequestArguments, ResponseBody};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, ExceptionInfo},
+};
+
+use super::query_threads::QueryThreads;
+
+/// Asks for the details of the exception the program stopped at, then queries the program like
+/// after any other stop.
+#[derive(Debug)]
+pub struct QueryExceptionInfo {
+    pub thread_id: i64,
+}
+
+impl DapStateHandler for QueryExceptionInfo {
+    fn next_requests(
+        &self,
+        _context: &DapContext,
+    ) -> Option<Box<[dap_types::types::RequestArguments]>> {
+        Some(Box::new([RequestArguments::exceptionInfo(
+            ExceptionInfoArguments {
+                thread_id: self.thread_id,
+            },
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &dap_types::types::ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::exceptionInfo(exception_info) => {
+                context.exception = Some(ExceptionInfo::from(exception_info));
+                Some(QueryThreads.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // what the stopped event told us about the exception is still better than nothing
+        Some(QueryThreads.into())
+    }
+}
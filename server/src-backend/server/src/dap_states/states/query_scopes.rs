@@ -2,7 +2,7 @@ use dap_types::types::{RequestArguments, ResponseBody};
 
 use crate::dap_states::{
     dap_state::{DapState, DapStateHandler},
-    dap_state_machine::{DapContext, ScopeInfo},
+    dap_state_machine::{DapContext, ProgramState, ScopeInfo},
 };
 
 use super::query_variables::QueryVariables;
@@ -10,6 +10,18 @@ use super::query_variables::QueryVariables;
 #[derive(Debug)]
 pub struct QueryScopes;
 
+impl QueryScopes {
+    /// Queries the scopes of every stack frame that has none yet, moving on to the variables right
+    /// away if there is no such frame.
+    pub fn start(context: &DapContext) -> DapState {
+        context
+            .program_state
+            .as_ref()
+            .and_then(all_scopes_received)
+            .unwrap_or_else(|| QueryScopes.into())
+    }
+}
+
 impl DapStateHandler for QueryScopes {
     fn next_requests(
         &self,
@@ -24,24 +36,24 @@ impl DapStateHandler for QueryScopes {
             tracing::warn!("QueryScopes state entered without threads in ProgramState")
         }
 
-        let next_scope_request = program_state
+        let scope_requests: Box<[_]> = program_state
             .threads
             .iter()
             .filter_map(|thread| thread.stack_frames.as_ref())
             .flatten()
-            .filter_map(|frame| {
-                frame.scopes.is_none().then_some(RequestArguments::scopes(
-                    dap_types::types::ScopesArguments { frame_id: frame.id },
-                ))
+            .filter(|frame| frame.scopes.is_none())
+            .map(|frame| {
+                RequestArguments::scopes(dap_types::types::ScopesArguments { frame_id: frame.id })
             })
-            .next();
+            .collect();
 
-        next_scope_request.map(|request| [request].into())
+        (!scope_requests.is_empty()).then_some(scope_requests)
     }
 
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         let program_state = context
@@ -49,43 +61,77 @@ impl DapStateHandler for QueryScopes {
             .as_mut()
             .expect("current state expects initialized program state");
 
-        let stack_frame_count = program_state
+        let (RequestArguments::scopes(arguments), ResponseBody::scopes(scopes)) =
+            (request, response)
+        else {
+            tracing::error!("Unexpected response: {:?}", response);
+            return None;
+        };
+
+        let Some(stack_frame) = program_state
             .threads
             .iter_mut()
             .filter_map(|thread| thread.stack_frames.as_mut())
             .flatten()
-            .count();
+            .find(|frame| frame.id == arguments.frame_id)
+        else {
+            tracing::warn!(
+                "Received scopes of unknown stack frame {}",
+                arguments.frame_id
+            );
+            return None;
+        };
 
-        let next_empty_stack_frame = program_state
+        stack_frame.scopes = scopes
+            .scopes
+            .iter()
+            .map(ScopeInfo::from)
+            .collect::<Vec<_>>()
+            .into();
+
+        all_scopes_received(program_state)
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        let RequestArguments::scopes(arguments) = request else {
+            return None;
+        };
+
+        let program_state = context
+            .program_state
+            .as_mut()
+            .expect("current state expects initialized program state");
+
+        if let Some(stack_frame) = program_state
             .threads
             .iter_mut()
             .filter_map(|thread| thread.stack_frames.as_mut())
             .flatten()
-            .enumerate()
-            .find(|(_, frame)| frame.scopes.is_none());
-
-        match response {
-            ResponseBody::scopes(scopes) => Some(next_empty_stack_frame.map_or(
-                QueryVariables::default().into(),
-                |(index, stack_frame)| {
-                    stack_frame.scopes = scopes
-                        .scopes
-                        .iter()
-                        .map(ScopeInfo::from)
-                        .collect::<Vec<_>>()
-                        .into();
-
-                    if index == stack_frame_count - 1 {
-                        QueryVariables::default().into()
-                    } else {
-                        QueryScopes.into()
-                    }
-                },
-            )),
-            _ => {
-                tracing::error!("Unexpected response: {:?}", response);
-                None
-            }
+            .find(|frame| frame.id == arguments.frame_id)
+        {
+            stack_frame.scopes.get_or_insert_default();
         }
+
+        all_scopes_received(program_state)
+    }
+}
+
+fn all_scopes_received(program_state: &ProgramState) -> Option<DapState> {
+    if program_state
+        .threads
+        .iter()
+        .filter_map(|thread| thread.stack_frames.as_ref())
+        .flatten()
+        .all(|frame| frame.scopes.is_some())
+    {
+        Some(QueryVariables::start(program_state))
+    } else {
+        // keep waiting for the scopes of the other stack frames
+        None
     }
 }
@@ -5,7 +5,7 @@ use crate::dap_states::{
     dap_state_machine::{DapContext, StackFrameInfo},
 };
 
-use super::query_scopes::QueryScopes;
+use super::query_step_in_targets::QueryStepInTargets;
 
 #[derive(Debug)]
 pub struct QueryStackTraces;
@@ -24,30 +24,27 @@ impl DapStateHandler for QueryStackTraces {
             tracing::warn!("QueryStackTraces state entered without threads in ProgramState")
         }
 
-        let next_stack_trace_request = program_state
+        let stack_trace_requests: Box<[_]> = program_state
             .threads
             .iter()
-            .filter_map(|thread| {
-                thread
-                    .stack_frames
-                    .is_none()
-                    .then_some(RequestArguments::stackTrace(
-                        dap_types::types::StackTraceArguments {
-                            thread_id: thread.id,
-                            format: None,
-                            levels: None,
-                            start_frame: None,
-                        },
-                    ))
+            .filter(|thread| thread.stack_frames.is_none())
+            .map(|thread| {
+                RequestArguments::stackTrace(dap_types::types::StackTraceArguments {
+                    thread_id: thread.id,
+                    format: None,
+                    levels: None,
+                    start_frame: None,
+                })
             })
-            .next();
+            .collect();
 
-        next_stack_trace_request.map(|request| [request].into())
+        (!stack_trace_requests.is_empty()).then_some(stack_trace_requests)
     }
 
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         let program_state = context
@@ -55,47 +52,93 @@ impl DapStateHandler for QueryStackTraces {
             .as_mut()
             .expect("current state expects initialized program state");
 
-        let thread_count = program_state.threads.len();
-
-        match response {
-            ResponseBody::stackTrace(stack_trace) => Some(
-                program_state
-                    .threads
-                    .iter_mut()
-                    .enumerate()
-                    .find(|(_, thread)| thread.stack_frames.is_none())
-                    .map_or(QueryScopes.into(), |(i, thread)| {
-                        let stack_frames: Vec<_> = stack_trace
-                            .stack_frames
-                            .iter()
-                            .map(StackFrameInfo::from)
-                            .collect();
-
-                        for stack_frame in &stack_frames {
-                            context
-                                .source_files
-                                .entry(stack_frame.file.clone())
-                                .or_insert_with_key(|key| {
-                                    std::fs::read_to_string(key)
-                                        .expect("debug adapter should return valid file path for stack frame")
-                                });
-                        }
-
-                        thread.stack_frames = Some(stack_frames);
-
-                        if i == thread_count - 1 {
-                            // if we just filled the last thread, continue with querying scopes
-                            QueryScopes.into()
-                        } else {
-                            // else continue with the next thread
-                            QueryStackTraces.into()
-                        }
-                    }),
-            ),
-            _ => {
-                tracing::error!("Unexpected response: {:?}", response);
-                None
-            }
+        let (RequestArguments::stackTrace(arguments), ResponseBody::stackTrace(stack_trace)) =
+            (request, response)
+        else {
+            tracing::error!("Unexpected response: {:?}", response);
+            return None;
+        };
+
+        let Some(thread) = program_state
+            .threads
+            .iter_mut()
+            .find(|thread| thread.id == arguments.thread_id)
+        else {
+            // e.g. the thread exited in the meantime
+            tracing::warn!(
+                "Received stack trace of unknown thread {}",
+                arguments.thread_id
+            );
+            return all_stack_traces_received(context);
+        };
+
+        let stack_frames: Vec<_> = stack_trace
+            .stack_frames
+            .iter()
+            .map(StackFrameInfo::from)
+            .collect();
+
+        for stack_frame in &stack_frames {
+            context
+                .source_files
+                .entry(stack_frame.file.clone())
+                .or_insert_with_key(|key| {
+                    // e.g. when replaying a session recorded on another machine
+                    std::fs::read_to_string(key).unwrap_or_else(|err| {
+                        tracing::warn!("Could not read source file {key}: {err}");
+                        String::new()
+                    })
+                });
         }
+
+        thread.stack_frames = Some(stack_frames);
+
+        all_stack_traces_received(context)
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        let RequestArguments::stackTrace(arguments) = request else {
+            return None;
+        };
+
+        let program_state = context
+            .program_state
+            .as_mut()
+            .expect("current state expects initialized program state");
+
+        // show the thread without stack frames instead of waiting forever
+        if let Some(thread) = program_state
+            .threads
+            .iter_mut()
+            .find(|thread| thread.id == arguments.thread_id)
+        {
+            thread.stack_frames.get_or_insert_default();
+        }
+
+        all_stack_traces_received(context)
+    }
+}
+
+fn all_stack_traces_received(context: &DapContext) -> Option<DapState> {
+    let program_state = context
+        .program_state
+        .as_ref()
+        .expect("current state expects initialized program state");
+
+    if program_state
+        .threads
+        .iter()
+        .all(|thread| thread.stack_frames.is_some())
+    {
+        // if we just filled the last thread, continue with querying scopes
+        Some(QueryStepInTargets::after_stack_traces(context))
+    } else {
+        // else keep waiting for the stack traces of the other threads
+        None
     }
 }
@@ -0,0 +1,82 @@
+use dap_types::types::{RequestArguments, ResponseBody, StepInTargetsArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, StepInTargetInfo},
+};
+
+use super::query_scopes::QueryScopes;
+
+/// Asks which calls on the current line of the active thread can be stepped into, so the user can
+/// pick one on lines like `Apply(Scale(vec))`.
+#[derive(Debug)]
+pub struct QueryStepInTargets {
+    pub frame_id: i64,
+}
+
+impl QueryStepInTargets {
+    /// The state following the stack traces, querying the step in targets of the current frame of
+    /// the active thread if the debug adapter supports it.
+    pub fn after_stack_traces(context: &DapContext) -> DapState {
+        let current_frame = context
+            .program_state
+            .as_ref()
+            .zip(context.active_thread)
+            .and_then(|(program_state, active_thread)| {
+                program_state
+                    .threads
+                    .iter()
+                    .find(|thread| thread.id == active_thread)
+            })
+            .and_then(|thread| thread.stack_frames.as_ref()?.first());
+
+        match current_frame {
+            Some(frame)
+                if context
+                    .supports(|capabilities| capabilities.supports_step_in_targets_request) =>
+            {
+                QueryStepInTargets { frame_id: frame.id }.into()
+            }
+            _ => QueryScopes::start(context),
+        }
+    }
+}
+
+impl DapStateHandler for QueryStepInTargets {
+    fn next_requests(&self, _context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([RequestArguments::stepInTargets(
+            StepInTargetsArguments {
+                frame_id: self.frame_id,
+            },
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::stepInTargets(body) => {
+                context.step_in_targets = body.targets.iter().map(StepInTargetInfo::from).collect();
+
+                Some(QueryScopes::start(context))
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // stepping in still works, the adapter just picks the target
+        Some(QueryScopes::start(context))
+    }
+}
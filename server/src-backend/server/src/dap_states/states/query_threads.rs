@@ -1,15 +1,47 @@
-use dap_types::types::{RequestArguments, ResponseBody};
+use dap_types::types::{RequestArguments, ResponseBody, StoppedEventBody};
 
 use crate::dap_states::{
     dap_state::{DapState, DapStateHandler},
-    dap_state_machine::{DapContext, ProgramState, VariableResolver},
+    dap_state_machine::{DapContext, ExceptionInfo, ProgramState, VariableResolver},
 };
 
-use super::query_stack_traces::QueryStackTraces;
+use super::{
+    query_exception_info::QueryExceptionInfo, query_stack_traces::QueryStackTraces,
+    remove_temporary_breakpoint::RemoveTemporaryBreakpoint, wait_for_user_input::WaitForUserInput,
+};
 
 #[derive(Debug)]
 pub struct QueryThreads;
 
+impl QueryThreads {
+    /// The state querying the program after it stopped as described by `event`.
+    pub fn after_stop(context: &mut DapContext, event: &StoppedEventBody) -> DapState {
+        if let Some(file) = context.breakpoints.take_temporary() {
+            return RemoveTemporaryBreakpoint {
+                file,
+                stopped: event.clone(),
+            }
+            .into();
+        }
+
+        if event.reason != "exception" {
+            return QueryThreads.into();
+        }
+
+        context.exception = Some(ExceptionInfo::from(event));
+
+        match event.thread_id {
+            Some(thread_id)
+                if context
+                    .supports(|capabilities| capabilities.supports_exception_info_request) =>
+            {
+                QueryExceptionInfo { thread_id }.into()
+            }
+            _ => QueryThreads.into(),
+        }
+    }
+}
+
 impl DapStateHandler for QueryThreads {
     fn next_requests(
         &self,
@@ -21,15 +53,32 @@ impl DapStateHandler for QueryThreads {
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
             ResponseBody::threads(threads) => {
-                let program_state = ProgramState::from_threads(&threads.threads);
-                context.active_thread =
-                    (program_state.threads.len() == 1).then(|| program_state.threads[0].id);
+                let mut program_state = ProgramState::from_threads(&threads.threads);
+
+                // keep the thread that stopped, the one the user stepped or the one selected
+                let is_known =
+                    |thread| program_state.threads.iter().any(|known| known.id == thread);
+                if !context.active_thread.is_some_and(is_known) {
+                    context.active_thread = program_state.threads.first().map(|thread| thread.id);
+                }
+
+                // threads that are still running can't tell where they are
+                if !context.all_threads_stopped {
+                    for thread in &mut program_state.threads {
+                        if Some(thread.id) != context.active_thread {
+                            thread.stack_frames = Some(Vec::new());
+                        }
+                    }
+                }
+
                 context.program_state = Some(program_state);
                 context.variable_resolver = VariableResolver::new();
+                context.request_failures.clear();
 
                 Some(QueryStackTraces.into())
             }
@@ -39,4 +88,14 @@ impl DapStateHandler for QueryThreads {
             }
         }
     }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // keep showing the last known program state, the user can try again by stepping
+        Some(WaitForUserInput.into())
+    }
 }
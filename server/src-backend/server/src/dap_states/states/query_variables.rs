@@ -1,4 +1,7 @@
+use std::collections::HashSet;
+
 use dap_types::types::{RequestArguments, ResponseBody};
+use itertools::{Either, Itertools as _};
 
 use crate::{
     dap_states::{
@@ -10,105 +13,146 @@ use crate::{
 
 use super::wait_for_user_input::WaitForUserInput;
 
+// upper bound for the variables requests sent per stop, so cyclic or huge object graphs can't keep
+// us querying forever
+pub(crate) const MAX_VARIABLE_REQUESTS: usize = 100;
+
+/// Queries the members of every scope and every heap variable reachable from them. All requests
+/// that are known at a time are sent at once, members discovered by a response are requested as
+/// soon as it arrives.
 #[derive(Debug, Default)]
 pub struct QueryVariables {
-    depth: usize,
+    /// references sent when this state is entered
+    requests: Vec<i64>,
+    /// references that were requested but not answered yet
+    pending: HashSet<i64>,
+    /// every reference requested since the program stopped
+    requested: HashSet<i64>,
 }
 
-enum NextRef<'a> {
-    Scope(&'a ScopeInfo),
-    Variable(i64),
+impl QueryVariables {
+    pub fn start(program_state: &ProgramState) -> DapState {
+        QueryVariables::default()
+            .request_unqueried(program_state)
+            .unwrap_or_else(|| WaitForUserInput.into())
+    }
+
+    fn continue_querying(&mut self, program_state: &ProgramState) -> Option<DapState> {
+        match self.request_unqueried(program_state) {
+            Some(next_state) => Some(next_state),
+            None if self.pending.is_empty() => Some(WaitForUserInput.into()),
+            None => None,
+        }
+    }
+
+    /// Returns the state requesting every scope or variable that hasn't been requested yet, if
+    /// there are any left within the request limit.
+    fn request_unqueried(&mut self, program_state: &ProgramState) -> Option<DapState> {
+        let mut unrequested = program_state
+            .unqueried_variables_references()
+            .filter(|reference| !self.requested.contains(reference))
+            .peekable();
+
+        unrequested.peek()?;
+
+        let budget = MAX_VARIABLE_REQUESTS.saturating_sub(self.requested.len());
+        if budget == 0 {
+            tracing::warn!("Recursive variable query depth limit reached");
+            return None;
+        }
+
+        let requests: Vec<_> = unrequested.unique().take(budget).collect();
+
+        let mut pending = std::mem::take(&mut self.pending);
+        let mut requested = std::mem::take(&mut self.requested);
+        pending.extend(&requests);
+        requested.extend(&requests);
+
+        Some(
+            QueryVariables {
+                requests,
+                pending,
+                requested,
+            }
+            .into(),
+        )
+    }
 }
 
-enum NextRefMut<'a> {
+enum RequestedRefMut<'a> {
     Scope(&'a mut ScopeInfo),
     Variable(&'a mut ScopeInfo, usize),
 }
 
 impl ProgramState {
-    fn next_variable_request(&self) -> Option<NextRef<'_>> {
+    fn unqueried_variables_references(&self) -> impl Iterator<Item = i64> {
         self.threads
             .iter()
             .filter_map(|thread| thread.stack_frames.as_ref())
             .flatten()
             .filter_map(|frame| frame.scopes.as_ref())
             .flatten()
-            .filter_map(|scope| match scope.variables {
-                Some(ref scope_variables) => scope_variables
-                    .iter()
-                    .filter_map(|variable| match variable {
+            .flat_map(|scope| match scope.variables {
+                Some(ref scope_variables) => Either::Left(scope_variables.iter().filter_map(
+                    |variable| match variable {
                         VariableInfo::Queried(..) => None,
-                        VariableInfo::Unqueried(variable) => {
-                            Some(NextRef::Variable(variable.reference))
-                        }
-                    })
-                    .next(),
-                None => Some(NextRef::Scope(scope)),
+                        VariableInfo::Unqueried(variable) => Some(variable.reference),
+                    },
+                )),
+                None => Either::Right(std::iter::once(scope.variables_reference)),
             })
-            .next()
     }
 
-    fn next_variable_request_mut(&mut self) -> Option<NextRefMut<'_>> {
+    /// Finds the scope or variable whose members were requested with `variables_reference`.
+    fn requested_variables_mut(&mut self, variables_reference: i64) -> Option<RequestedRefMut<'_>> {
         self.threads
             .iter_mut()
             .filter_map(|thread| thread.stack_frames.as_mut())
             .flatten()
             .filter_map(|frame| frame.scopes.as_mut())
             .flatten()
-            .filter_map(|scope| match scope.variables.clone() {
-                Some(ref scope_variables) => {
-                    let variable_index = scope_variables
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(i, variable)| match variable {
-                            VariableInfo::Queried(..) => None,
-                            VariableInfo::Unqueried(..) => Some(i),
-                        })
-                        .next();
-
-                    variable_index.map(|i| NextRefMut::Variable(scope, i))
+            .find_map(|scope| match scope.variables {
+                None if scope.variables_reference == variables_reference => {
+                    Some(RequestedRefMut::Scope(scope))
                 }
-                None => Some(NextRefMut::Scope(scope)),
+                None => None,
+                Some(ref scope_variables) => scope_variables
+                    .iter()
+                    .position(|variable| match variable {
+                        VariableInfo::Queried(..) => false,
+                        VariableInfo::Unqueried(data) => data.reference == variables_reference,
+                    })
+                    .map(|i| RequestedRefMut::Variable(scope, i)),
             })
-            .next()
     }
 }
 
 impl DapStateHandler for QueryVariables {
     fn next_requests(
         &self,
-        context: &DapContext,
+        _context: &DapContext,
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
-        let program_state = context
-            .program_state
-            .as_ref()
-            .expect("current state expects initialized program state");
-
-        let next_variables_reference =
-            program_state
-                .next_variable_request()
-                .map(|request| match request {
-                    NextRef::Scope(scope) => scope.variables_reference,
-                    NextRef::Variable(reference) => reference,
-                });
-
-        next_variables_reference.map(|reference| {
-            [RequestArguments::variables(
-                dap_types::types::VariablesArguments {
+        let requests: Box<[_]> = self
+            .requests
+            .iter()
+            .map(|&reference| {
+                RequestArguments::variables(dap_types::types::VariablesArguments {
                     variables_reference: reference,
                     count: None,
                     filter: None,
                     format: None,
                     start: None,
-                },
-            )]
-            .into()
-        })
+                })
+            })
+            .collect();
+
+        (!requests.is_empty()).then_some(requests)
     }
 
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         let program_state = context
@@ -118,17 +162,26 @@ impl DapStateHandler for QueryVariables {
 
         let resolver = &mut context.variable_resolver;
 
-        let ResponseBody::variables(response) = response else {
+        let (RequestArguments::variables(arguments), ResponseBody::variables(response)) =
+            (request, response)
+        else {
             tracing::error!("Unexpected response: {:?}", response);
             return None;
         };
 
-        let next_requested = program_state.next_variable_request_mut();
+        self.pending.remove(&arguments.variables_reference);
+
+        let Some(requested) = program_state.requested_variables_mut(arguments.variables_reference)
+        else {
+            tracing::warn!(
+                "Received variables of unknown reference {}",
+                arguments.variables_reference
+            );
+            return self.pending.is_empty().then(|| WaitForUserInput.into());
+        };
 
-        match next_requested
-            .expect("received response even though we have no more variable requests")
-        {
-            NextRefMut::Scope(scope) => {
+        match requested {
+            RequestedRefMut::Scope(scope) => {
                 scope.variables = response
                     .variables
                     .iter()
@@ -136,18 +189,15 @@ impl DapStateHandler for QueryVariables {
                     .collect::<Vec<_>>()
                     .into()
             }
-            NextRefMut::Variable(scope, variable_index) => {
+            RequestedRefMut::Variable(scope, variable_index) => {
                 let variables = scope
                     .variables
                     .as_mut()
-                    .expect("next ref must not return variable if scope has no variables");
+                    .expect("requested ref must not return variable if scope has no variables");
 
                 // TODO: can we get rid of this clone?
                 variables[variable_index] = variables[variable_index].clone().into_queried();
-                let variables_reference = match &variables[variable_index] {
-                    VariableInfo::Queried(variable_info_data) => variable_info_data.reference,
-                    VariableInfo::Unqueried(variable_info_data) => variable_info_data.reference,
-                };
+                let variables_reference = arguments.variables_reference;
 
                 variables.append(
                     &mut response
@@ -171,20 +221,40 @@ impl DapStateHandler for QueryVariables {
             }
         }
 
-        let next_requested = program_state.next_variable_request();
+        self.continue_querying(program_state)
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        let RequestArguments::variables(arguments) = request else {
+            return None;
+        };
 
-        match next_requested {
-            None => Some(WaitForUserInput.into()),
-            Some(..) if self.depth > 100 => {
-                tracing::warn!("Recursive variable query depth limit reached");
-                Some(WaitForUserInput.into())
+        let program_state = context
+            .program_state
+            .as_mut()
+            .expect("current state expects initialized program state");
+
+        self.pending.remove(&arguments.variables_reference);
+
+        // give up on this reference, show it without members
+        match program_state.requested_variables_mut(arguments.variables_reference) {
+            Some(RequestedRefMut::Scope(scope)) => scope.variables = Some(Vec::new()),
+            Some(RequestedRefMut::Variable(scope, variable_index)) => {
+                let variables = scope
+                    .variables
+                    .as_mut()
+                    .expect("requested ref must not return variable if scope has no variables");
+
+                variables[variable_index] = variables[variable_index].clone().into_queried();
             }
-            Some(..) => Some(
-                QueryVariables {
-                    depth: self.depth + 1,
-                }
-                .into(),
-            ),
+            None => (),
         }
+
+        self.continue_querying(program_state)
     }
 }
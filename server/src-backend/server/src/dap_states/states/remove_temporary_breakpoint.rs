@@ -0,0 +1,53 @@
+use dap_types::types::{RequestArguments, ResponseBody, StoppedEventBody};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::{query_threads::QueryThreads, set_breakpoints::SetBreakpoints};
+
+/// Sends the user's breakpoints of `file` again after the program stopped, removing the
+/// temporary one a run to a line added, then goes on querying the program.
+#[derive(Debug)]
+pub struct RemoveTemporaryBreakpoint {
+    pub file: String,
+    pub stopped: StoppedEventBody,
+}
+
+impl DapStateHandler for RemoveTemporaryBreakpoint {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([SetBreakpoints::request(context, &self.file, [])]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::setBreakpoints(body) => {
+                context
+                    .breakpoints
+                    .set_verified(&self.file, &body.breakpoints);
+
+                Some(QueryThreads::after_stop(context, &self.stopped))
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program stopped either way, so show where
+        Some(QueryThreads::after_stop(context, &self.stopped))
+    }
+}
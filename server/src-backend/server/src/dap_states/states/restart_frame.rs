@@ -0,0 +1,50 @@
+use dap_types::types::{RequestArguments, ResponseBody, RestartFrameArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::{wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput};
+
+/// Runs a frame again from its start, e.g. after stepping past the interesting line. The debug
+/// adapter reports the new location with a `stopped` event.
+#[derive(Debug)]
+pub struct RestartFrame {
+    pub frame_id: i64,
+}
+
+impl DapStateHandler for RestartFrame {
+    fn next_requests(&self, _context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([RequestArguments::restartFrame(
+            RestartFrameArguments {
+                frame_id: self.frame_id,
+            },
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::restartFrame => Some(WaitForBreakpointHit.into()),
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program did not move, so the current visualization is still valid
+        Some(WaitForUserInput.into())
+    }
+}
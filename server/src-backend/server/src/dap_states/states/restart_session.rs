@@ -0,0 +1,52 @@
+use dap_types::types::{DisconnectArguments, RequestArguments, ResponseBody, TerminateArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, DebugTarget},
+};
+
+/// Ends the debuggee, or detaches from it if it was attached to, so the session can be started
+/// over. The session is over once the debug adapter sent `terminated` or answered `disconnect`,
+/// after which the dap client connects to a new debug adapter.
+#[derive(Debug)]
+pub struct RestartSession;
+
+impl DapStateHandler for RestartSession {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        let terminate = context.supports(|capabilities| capabilities.supports_terminate_request);
+
+        let request = match &context.target {
+            // the process belongs to the user, it is attached to again after detaching
+            DebugTarget::Attach { .. } => RequestArguments::disconnect(Some(DisconnectArguments {
+                restart: Some(false),
+                terminate_debuggee: Some(false),
+                suspend_debuggee: None,
+            })),
+            // terminate gives the debuggee a chance to shut down gracefully
+            DebugTarget::Launch { .. } if terminate => {
+                RequestArguments::terminate(Some(TerminateArguments {
+                    restart: Some(false),
+                }))
+            }
+            DebugTarget::Launch { .. } => RequestArguments::disconnect(Some(DisconnectArguments {
+                restart: Some(false),
+                terminate_debuggee: Some(true),
+                suspend_debuggee: None,
+            })),
+        };
+
+        Some(Box::new([request]))
+    }
+
+    fn handle_response(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        // answers to requests of the state we came from don't matter anymore
+        tracing::debug!("Ignoring response while restarting: {:?}", response);
+
+        None
+    }
+}
@@ -0,0 +1,121 @@
+use dap_types::types::{
+    GotoTargetsArguments, RequestArguments, ResponseBody, Source, SourceBreakpoint,
+};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::{DapContext, RequestFailure},
+};
+
+use super::{
+    continue_::Continue, goto::Goto, set_breakpoints::SetBreakpoints,
+    wait_for_user_input::WaitForUserInput,
+};
+
+/// Lets `thread` run until it reaches `line`. Adapters that support it jump there with `goto`,
+/// otherwise the program continues to a temporary breakpoint, which is removed again once the
+/// program stopped.
+#[derive(Debug)]
+pub struct RunToLine {
+    pub file: String,
+    pub line: i64,
+    pub thread: i64,
+}
+
+impl RunToLine {
+    fn supports_goto(context: &DapContext) -> bool {
+        context.supports(|capabilities| capabilities.supports_goto_targets_request)
+    }
+
+    /// A user breakpoint on the line stops the program just as well.
+    fn needs_temporary_breakpoint(&self, context: &DapContext) -> bool {
+        !context.breakpoints.is_set(&self.file, self.line)
+    }
+}
+
+impl DapStateHandler for RunToLine {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        if RunToLine::supports_goto(context) {
+            return Some(Box::new([RequestArguments::gotoTargets(
+                GotoTargetsArguments {
+                    source: Source {
+                        path: Some(self.file.clone()),
+                        ..Default::default()
+                    },
+                    line: self.line,
+                    column: None,
+                },
+            )]));
+        }
+
+        let temporary = self
+            .needs_temporary_breakpoint(context)
+            .then_some(SourceBreakpoint {
+                line: self.line,
+                column: None,
+                condition: None,
+                hit_condition: None,
+                log_message: None,
+                mode: None,
+            });
+
+        Some(Box::new([SetBreakpoints::request(
+            context, &self.file, temporary,
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::gotoTargets(body) => match body.targets.first() {
+                Some(target) => Some(
+                    Goto {
+                        thread: self.thread,
+                        target_id: target.id,
+                    }
+                    .into(),
+                ),
+                None => {
+                    let message = format!("no code to run to at {}:{}", self.file, self.line);
+                    tracing::warn!("{message}");
+                    context
+                        .request_failures
+                        .push(RequestFailure::new(request, message));
+
+                    Some(WaitForUserInput.into())
+                }
+            },
+            ResponseBody::setBreakpoints(body) => {
+                let mut verified = body.breakpoints.as_slice();
+
+                // the temporary breakpoint was sent last
+                if self.needs_temporary_breakpoint(context) {
+                    verified = verified.split_last().map_or(verified, |(_, user)| user);
+                    context.breakpoints.set_temporary(self.file.clone());
+                }
+
+                context.breakpoints.set_verified(&self.file, verified);
+
+                Some(Continue(self.thread).into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program did not move, so the current visualization is still valid
+        Some(WaitForUserInput.into())
+    }
+}
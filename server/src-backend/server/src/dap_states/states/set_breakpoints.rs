@@ -0,0 +1,105 @@
+use dap_types::types::{
+    RequestArguments, ResponseBody, SetBreakpointsArguments, Source, SourceBreakpoint,
+};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::wait_for_user_input::WaitForUserInput;
+
+/// Sends the breakpoints of a source file to the debug adapter after the user changed them.
+#[derive(Debug)]
+pub struct SetBreakpoints {
+    pub file: String,
+}
+
+impl SetBreakpoints {
+    /// The request sending the breakpoints of `file` along with `extra` ones, which are not
+    /// recorded as the user's.
+    pub fn request(
+        context: &DapContext,
+        file: &str,
+        extra: impl IntoIterator<Item = SourceBreakpoint>,
+    ) -> RequestArguments {
+        let supports_conditions =
+            context.supports(|capabilities| capabilities.supports_conditional_breakpoints);
+        let supports_hit_conditions =
+            context.supports(|capabilities| capabilities.supports_hit_conditional_breakpoints);
+        let supports_log_points = context.supports(|capabilities| capabilities.supports_log_points);
+
+        let mut breakpoints = context.breakpoints.source_breakpoints(file);
+
+        // adapters are free to treat these as plain breakpoints, which is worse than telling the
+        // user right away
+        for breakpoint in &mut breakpoints {
+            if !supports_conditions && let Some(condition) = breakpoint.condition.take() {
+                tracing::warn!("Debug adapter does not support conditions, ignoring {condition:?}");
+            }
+
+            if !supports_hit_conditions && let Some(hit_condition) = breakpoint.hit_condition.take()
+            {
+                tracing::warn!(
+                    "Debug adapter does not support hit conditions, ignoring {hit_condition:?}"
+                );
+            }
+
+            if !supports_log_points && let Some(log_message) = breakpoint.log_message.take() {
+                tracing::warn!(
+                    "Debug adapter does not support log points, ignoring {log_message:?}"
+                );
+            }
+        }
+
+        breakpoints.extend(extra);
+
+        RequestArguments::setBreakpoints(SetBreakpointsArguments {
+            source: Source {
+                path: Some(file.into()),
+                ..Default::default()
+            },
+            breakpoints: Some(breakpoints),
+            lines: None,
+            source_modified: Some(false),
+        })
+    }
+}
+
+impl DapStateHandler for SetBreakpoints {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([SetBreakpoints::request(context, &self.file, [])]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::setBreakpoints(body) => {
+                context
+                    .breakpoints
+                    .set_verified(&self.file, &body.breakpoints);
+
+                Some(WaitForUserInput.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        message: &str,
+    ) -> Option<DapState> {
+        context.breakpoints.set_unverified(&self.file, message);
+
+        Some(WaitForUserInput.into())
+    }
+}
@@ -0,0 +1,52 @@
+use dap_types::types::{RequestArguments, ResponseBody, SetDataBreakpointsArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::wait_for_user_input::WaitForUserInput;
+
+/// Sends all data breakpoints to the debug adapter after the user changed them.
+#[derive(Debug)]
+pub struct SetDataBreakpoints;
+
+impl DapStateHandler for SetDataBreakpoints {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        Some(Box::new([RequestArguments::setDataBreakpoints(
+            SetDataBreakpointsArguments {
+                breakpoints: context.breakpoints.data_breakpoints(),
+            },
+        )]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::setDataBreakpoints(body) => {
+                context.breakpoints.set_data_verified(&body.breakpoints);
+
+                Some(WaitForUserInput.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        message: &str,
+    ) -> Option<DapState> {
+        context.breakpoints.set_data_unverified(message);
+
+        Some(WaitForUserInput.into())
+    }
+}
@@ -0,0 +1,70 @@
+use dap_types::types::{RequestArguments, ResponseBody, SetFunctionBreakpointsArguments};
+
+use crate::dap_states::{
+    dap_state::{DapState, DapStateHandler},
+    dap_state_machine::DapContext,
+};
+
+use super::wait_for_user_input::WaitForUserInput;
+
+/// Sends all function breakpoints to the debug adapter after the user changed them.
+#[derive(Debug)]
+pub struct SetFunctionBreakpoints;
+
+impl SetFunctionBreakpoints {
+    /// The request sending the function breakpoints of `context`, also used before the
+    /// configuration is done.
+    pub fn request(context: &DapContext) -> RequestArguments {
+        let supports_conditions =
+            context.supports(|capabilities| capabilities.supports_conditional_breakpoints);
+
+        let mut breakpoints = context.breakpoints.function_breakpoints();
+
+        for breakpoint in &mut breakpoints {
+            if !supports_conditions && let Some(condition) = breakpoint.condition.take() {
+                tracing::warn!("Debug adapter does not support conditions, ignoring {condition:?}");
+            }
+        }
+
+        RequestArguments::setFunctionBreakpoints(SetFunctionBreakpointsArguments { breakpoints })
+    }
+}
+
+impl DapStateHandler for SetFunctionBreakpoints {
+    fn next_requests(&self, context: &DapContext) -> Option<Box<[RequestArguments]>> {
+        // adapters without support answer with an error, which marks the breakpoints unverified
+        Some(Box::new([SetFunctionBreakpoints::request(context)]))
+    }
+
+    fn handle_response(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        response: &ResponseBody,
+    ) -> Option<DapState> {
+        match response {
+            ResponseBody::setFunctionBreakpoints(body) => {
+                context
+                    .breakpoints
+                    .set_functions_verified(&body.breakpoints);
+
+                Some(WaitForUserInput.into())
+            }
+            _ => {
+                tracing::error!("Unexpected response: {:?}", response);
+                None
+            }
+        }
+    }
+
+    fn handle_request_failure(
+        &mut self,
+        context: &mut DapContext,
+        _request: &RequestArguments,
+        message: &str,
+    ) -> Option<DapState> {
+        context.breakpoints.set_functions_unverified(message);
+
+        Some(WaitForUserInput.into())
+    }
+}
@@ -1,35 +1,42 @@
 use dap_types::types::{NextArguments, RequestArguments};
 
-use crate::dap_states::{
-    dap_state::{DapState, DapStateHandler},
-    dap_state_machine::DapContext,
+use crate::{
+    dap_states::{
+        dap_state::{DapState, DapStateHandler},
+        dap_state_machine::DapContext,
+    },
+    user_request::SteppingGranularity,
 };
 
-use super::wait_for_breakpoint_hit::WaitForBreakpointHit;
+use super::{wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput};
 
 #[derive(Debug)]
-pub struct Step(pub i64);
+pub struct Step {
+    pub thread: i64,
+    pub granularity: Option<SteppingGranularity>,
+}
 
 impl DapStateHandler for Step {
     fn next_requests(
         &self,
-        _context: &DapContext,
+        context: &DapContext,
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
         Some(Box::new([RequestArguments::next(NextArguments {
-            thread_id: self.0,
+            thread_id: self.thread,
             single_thread: true.into(),
-            granularity: None,
+            granularity: context.stepping_granularity(self.granularity),
         })]))
     }
 
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
             dap_types::types::ResponseBody::next(..) => {
-                context.active_thread = Some(self.0);
+                context.active_thread = Some(self.thread);
                 Some(WaitForBreakpointHit.into())
             },
             _ => {
@@ -38,4 +45,14 @@ impl DapStateHandler for Step {
             }
         }
     }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program did not move, so the current visualization is still valid
+        Some(WaitForUserInput.into())
+    }
 }
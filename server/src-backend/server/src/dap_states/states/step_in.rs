@@ -1,36 +1,45 @@
 use dap_types::types::{RequestArguments, StepInArguments};
 
-use crate::dap_states::{
-    dap_state::{DapState, DapStateHandler},
-    dap_state_machine::DapContext,
+use crate::{
+    dap_states::{
+        dap_state::{DapState, DapStateHandler},
+        dap_state_machine::DapContext,
+    },
+    user_request::SteppingGranularity,
 };
 
-use super::wait_for_breakpoint_hit::WaitForBreakpointHit;
+use super::{wait_for_breakpoint_hit::WaitForBreakpointHit, wait_for_user_input::WaitForUserInput};
 
 #[derive(Debug)]
-pub struct StepIn(pub i64);
+pub struct StepIn {
+    pub thread: i64,
+    /// one of the targets found by `stepInTargets`, the adapter decides if not set
+    pub target: Option<i64>,
+    pub granularity: Option<SteppingGranularity>,
+}
 
 impl DapStateHandler for StepIn {
     fn next_requests(
         &self,
-        _context: &DapContext,
+        context: &DapContext,
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
         Some(Box::new([RequestArguments::stepIn(StepInArguments {
-            thread_id: self.0,
-            target_id: None,
+            thread_id: self.thread,
+            target_id: self.target,
             single_thread: true.into(),
-            granularity: None,
+            granularity: context.stepping_granularity(self.granularity),
         })]))
     }
 
     fn handle_response(
         &mut self,
         context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
             dap_types::types::ResponseBody::stepIn(..) => {
-                context.active_thread = Some(self.0);
+                context.active_thread = Some(self.thread);
                 Some(WaitForBreakpointHit.into())
             }
             _ => {
@@ -39,4 +48,14 @@ impl DapStateHandler for StepIn {
             }
         }
     }
+
+    fn handle_request_failure(
+        &mut self,
+        _context: &mut DapContext,
+        _request: &RequestArguments,
+        _message: &str,
+    ) -> Option<DapState> {
+        // the program did not move, so the current visualization is still valid
+        Some(WaitForUserInput.into())
+    }
 }
@@ -1,9 +1,9 @@
 use dap_types::types::RequestArguments;
 
-use crate::{dap_states::{
+use crate::dap_states::{
     dap_state::{DapState, DapStateHandler},
     dap_state_machine::DapContext,
-}, debug_adapters::DebugAdapter};
+};
 
 use super::initialized::Initialized;
 
@@ -17,9 +17,7 @@ impl DapStateHandler for Uninitialized {
     ) -> Option<Box<[dap_types::types::RequestArguments]>> {
         Some(Box::new([RequestArguments::initialize(
             dap_types::types::InitializeRequestArguments {
-                adapter_id: match context.debug_adapter {
-                    DebugAdapter::NetCoreDbg => "coreclr".into(),
-                },
+                adapter_id: context.debug_adapter.adapter_id().into(),
                 client_id: Some("dapviz".into()),
                 client_name: Some("dapviz".into()),
                 columns_start_at_1: true.into(),
@@ -42,11 +40,15 @@ impl DapStateHandler for Uninitialized {
 
     fn handle_response(
         &mut self,
-        _context: &mut DapContext,
+        context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         match response {
-            dap_types::types::ResponseBody::initialize(..) => Some(Initialized.into()),
+            dap_types::types::ResponseBody::initialize(capabilities) => {
+                context.capabilities = Some(capabilities.clone());
+                Some(Initialized.into())
+            }
             _ => {
                 tracing::error!("Unexpected response: {:?}", response);
                 None
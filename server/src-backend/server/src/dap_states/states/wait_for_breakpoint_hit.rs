@@ -1,9 +1,12 @@
-use crate::dap_states::{
-    dap_state::{DapState, DapStateHandler},
-    dap_state_machine::DapContext,
+use crate::{
+    dap_states::{
+        dap_state::{DapState, DapStateHandler},
+        dap_state_machine::DapContext,
+    },
+    user_request::UserRequest,
 };
 
-use super::query_threads::QueryThreads;
+use super::{pause::Pause, query_threads::QueryThreads};
 
 #[derive(Debug)]
 pub struct WaitForBreakpointHit;
@@ -18,18 +21,35 @@ impl DapStateHandler for WaitForBreakpointHit {
 
     fn handle_event(
         &mut self,
-        _context: &mut DapContext,
+        context: &mut DapContext,
         event: &dap_types::types::EventBody,
     ) -> Option<DapState> {
         match event {
-            dap_types::types::EventBody::stopped(..) => Some(QueryThreads.into()),
+            dap_types::types::EventBody::stopped(stopped) => {
+                Some(QueryThreads::after_stop(context, stopped))
+            }
             _ => None,
         }
     }
 
+    fn handle_user_request(
+        &mut self,
+        _context: &mut DapContext,
+        request: &UserRequest,
+    ) -> Option<DapState> {
+        match request {
+            UserRequest::Pause(thread_id) => Some(Pause::thread(*thread_id).into()),
+            _ => {
+                tracing::debug!("Ignoring {request:?} while the program is running");
+                None
+            }
+        }
+    }
+
     fn handle_response(
         &mut self,
         _context: &mut DapContext,
+        _request: &dap_types::types::RequestArguments,
         _response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         None
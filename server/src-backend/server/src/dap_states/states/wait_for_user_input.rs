@@ -1,3 +1,5 @@
+use dap_types::types::RequestArguments;
+
 use crate::{
     dap_states::{
         dap_state::{DapState, DapStateHandler},
@@ -6,6 +8,8 @@ use crate::{
     user_request::UserRequest,
 };
 
+use super::query_step_in_targets::QueryStepInTargets;
+
 #[derive(Debug)]
 pub struct WaitForUserInput;
 
@@ -17,13 +21,75 @@ impl DapStateHandler for WaitForUserInput {
         None
     }
 
-    fn handle_user_request(&mut self, request: &UserRequest) -> Option<DapState> {
+    fn handle_user_request(
+        &mut self,
+        context: &mut DapContext,
+        request: &UserRequest,
+    ) -> Option<DapState> {
+        // the next state sends the changed breakpoints
+        match request {
+            UserRequest::SetBreakpoint(breakpoint) => context.breakpoints.set(breakpoint.clone()),
+            UserRequest::ClearBreakpoint { file, line } => context.breakpoints.clear(file, *line),
+            UserRequest::ToggleBreakpoint(breakpoint) => {
+                context.breakpoints.toggle(breakpoint.clone())
+            }
+            UserRequest::SetFunctionBreakpoint(breakpoint) => {
+                context.breakpoints.set_function(breakpoint.clone())
+            }
+            UserRequest::ClearFunctionBreakpoint { name } => {
+                context.breakpoints.clear_function(name)
+            }
+            UserRequest::SetDataBreakpoint { name, .. }
+                if !context.supports(|capabilities| capabilities.supports_data_breakpoints) =>
+            {
+                tracing::warn!(
+                    "Debug adapter does not support data breakpoints, can't watch {name}"
+                );
+                return None;
+            }
+            UserRequest::ClearDataBreakpoint { data_id } => context.breakpoints.clear_data(data_id),
+            UserRequest::SelectThread { thread } => {
+                let is_known = context
+                    .program_state
+                    .as_ref()
+                    .is_some_and(|state| state.threads.iter().any(|known| known.id == *thread));
+
+                if !is_known {
+                    tracing::warn!("Can't select unknown thread {thread}");
+                    return None;
+                }
+
+                context.active_thread = Some(*thread);
+                context.step_in_targets.clear();
+
+                // the step in targets shown are the ones of the selected thread
+                return Some(QueryStepInTargets::after_stack_traces(context));
+            }
+            UserRequest::RestartFrame { frame_id }
+                if !context.supports(|capabilities| capabilities.supports_restart_frame) =>
+            {
+                tracing::warn!("Debug adapter does not support restarting frame {frame_id}");
+                return None;
+            }
+            // the program is stopped already
+            UserRequest::Pause(..) => return None,
+            UserRequest::Step { .. }
+            | UserRequest::StepIn { .. }
+            | UserRequest::StepOut { .. }
+            | UserRequest::Continue(..)
+            | UserRequest::SetDataBreakpoint { .. }
+            | UserRequest::RunToLine { .. }
+            | UserRequest::RestartFrame { .. }
+            | UserRequest::RestartSession => (),
+        }
+
         Some(request.into())
     }
 
     fn handle_response(
         &mut self,
         _context: &mut DapContext,
+        _request: &RequestArguments,
         response: &dap_types::types::ResponseBody,
     ) -> Option<DapState> {
         tracing::error!("Unexpected DAP Response: {}", response);
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use crate::user_request::{FunctionBreakpoint, LineBreakpoint, UserRequest};
+
+// enough for a burst of clicks, anything beyond that is most likely a stuck key
+const MAX_QUEUED_USER_REQUESTS: usize = 32;
+
+/// User requests that arrived while the debug adapter was busy, applied in order once the program
+/// waits for user input again.
+///
+/// Requests made pointless by a later one are dropped right away. Only requests queued since the
+/// last step or continue are considered for that, as the ones before it decide how far it goes.
+#[derive(Debug, Default)]
+pub struct UserRequestQueue {
+    requests: VecDeque<UserRequest>,
+}
+
+impl UserRequestQueue {
+    pub fn push(&mut self, mut request: UserRequest) {
+        let moves_program = self.requests.iter().any(UserRequest::moves_program);
+
+        match &mut request {
+            // these refer to the current stop, which is over by the time they are applied
+            UserRequest::SetDataBreakpoint { .. } | UserRequest::RestartFrame { .. }
+                if moves_program =>
+            {
+                tracing::warn!("Dropping {request:?}, the stop it refers to will be over");
+                return;
+            }
+            UserRequest::StepIn { target, .. } if moves_program => {
+                if let Some(target) = target.take() {
+                    tracing::warn!("Dropping step in target {target} of a stop that will be over");
+                }
+            }
+            UserRequest::SelectThread { .. } => {
+                self.retain_since_last_move(|queued| {
+                    !matches!(queued, UserRequest::SelectThread { .. })
+                });
+            }
+            UserRequest::SetBreakpoint(LineBreakpoint { file, line, .. })
+            | UserRequest::ClearBreakpoint { file, line } => {
+                let (file, line) = (file.clone(), *line);
+                self.retain_since_last_move(|queued| !is_on_line(queued, &file, line));
+            }
+            UserRequest::ToggleBreakpoint(LineBreakpoint { file, line, .. }) => {
+                // toggling twice leaves everything as it is, unlike setting or clearing before
+                if let Some(UserRequest::ToggleBreakpoint(queued)) = self.requests.back()
+                    && queued.file == *file
+                    && queued.line == *line
+                {
+                    self.requests.pop_back();
+                    return;
+                }
+            }
+            UserRequest::SetFunctionBreakpoint(FunctionBreakpoint { name, .. })
+            | UserRequest::ClearFunctionBreakpoint { name } => {
+                let name = name.clone();
+                self.retain_since_last_move(|queued| {
+                    !matches!(
+                        queued,
+                        UserRequest::SetFunctionBreakpoint(FunctionBreakpoint { name: queued, .. })
+                        | UserRequest::ClearFunctionBreakpoint { name: queued }
+                            if *queued == name
+                    )
+                });
+            }
+            _ => (),
+        }
+
+        if self.requests.len() == MAX_QUEUED_USER_REQUESTS {
+            tracing::warn!("Too many queued user requests, dropping {request:?}");
+            return;
+        }
+
+        self.requests.push_back(request);
+    }
+
+    pub fn pop(&mut self) -> Option<UserRequest> {
+        self.requests.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+
+    fn retain_since_last_move(&mut self, keep: impl Fn(&UserRequest) -> bool) {
+        let start = self
+            .requests
+            .iter()
+            .rposition(UserRequest::moves_program)
+            .map_or(0, |index| index + 1);
+
+        let since_last_move = self.requests.split_off(start);
+        self.requests
+            .extend(since_last_move.into_iter().filter(|queued| keep(queued)));
+    }
+}
+
+/// Whether `request` changes the line breakpoint on `line`.
+fn is_on_line(request: &UserRequest, file: &str, line: i64) -> bool {
+    match request {
+        UserRequest::SetBreakpoint(breakpoint) | UserRequest::ToggleBreakpoint(breakpoint) => {
+            breakpoint.file == file && breakpoint.line == line
+        }
+        UserRequest::ClearBreakpoint {
+            file: queued_file,
+            line: queued_line,
+        } => queued_file == file && *queued_line == line,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UserRequestQueue;
+    use crate::user_request::{LineBreakpoint, UserRequest};
+
+    fn step() -> UserRequest {
+        UserRequest::Step {
+            thread: 1,
+            granularity: None,
+        }
+    }
+
+    fn toggle_breakpoint(line: i64) -> UserRequest {
+        UserRequest::ToggleBreakpoint(LineBreakpoint {
+            file: "/src/Program.cs".into(),
+            line,
+            condition: None,
+            hit_condition: None,
+            log_message: None,
+        })
+    }
+
+    fn set_breakpoint(line: i64) -> UserRequest {
+        match toggle_breakpoint(line) {
+            UserRequest::ToggleBreakpoint(breakpoint) => UserRequest::SetBreakpoint(breakpoint),
+            _ => unreachable!(),
+        }
+    }
+
+    fn clear_breakpoint(line: i64) -> UserRequest {
+        UserRequest::ClearBreakpoint {
+            file: "/src/Program.cs".into(),
+            line,
+        }
+    }
+
+    fn drain(mut queue: UserRequestQueue) -> Vec<UserRequest> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn keeps_every_step_in_order() {
+        let mut queue = UserRequestQueue::default();
+
+        for _ in 0..5 {
+            queue.push(step());
+        }
+        queue.push(UserRequest::Continue(1));
+
+        assert_eq!(queue.len(), 6);
+        assert_eq!(drain(queue).last(), Some(&UserRequest::Continue(1)));
+    }
+
+    #[test]
+    fn coalesces_requests_since_last_step() {
+        let mut queue = UserRequestQueue::default();
+
+        queue.push(UserRequest::SelectThread { thread: 1 });
+        queue.push(toggle_breakpoint(3));
+        queue.push(step());
+        queue.push(toggle_breakpoint(3));
+        queue.push(toggle_breakpoint(3));
+        queue.push(UserRequest::SelectThread { thread: 2 });
+        queue.push(UserRequest::SelectThread { thread: 3 });
+
+        assert_eq!(
+            drain(queue),
+            [
+                UserRequest::SelectThread { thread: 1 },
+                toggle_breakpoint(3),
+                step(),
+                UserRequest::SelectThread { thread: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_references_to_stops_that_will_be_over() {
+        let mut queue = UserRequestQueue::default();
+
+        queue.push(step());
+        queue.push(UserRequest::SetDataBreakpoint {
+            reference: 4,
+            name: "count".into(),
+        });
+        queue.push(UserRequest::StepIn {
+            thread: 1,
+            target: Some(2),
+            granularity: None,
+        });
+
+        assert_eq!(
+            drain(queue),
+            [
+                step(),
+                UserRequest::StepIn {
+                    thread: 1,
+                    target: None,
+                    granularity: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn toggles_after_setting_or_clearing_the_same_line() {
+        let mut queue = UserRequestQueue::default();
+
+        queue.push(set_breakpoint(3));
+        queue.push(toggle_breakpoint(3));
+        queue.push(clear_breakpoint(5));
+        queue.push(toggle_breakpoint(5));
+
+        assert_eq!(
+            drain(queue),
+            [
+                set_breakpoint(3),
+                toggle_breakpoint(3),
+                clear_breakpoint(5),
+                toggle_breakpoint(5),
+            ]
+        );
+    }
+}
@@ -2,7 +2,16 @@ use std::collections::BTreeMap;
 
 use serde::Serialize;
 
-use crate::dap_states::dap_state_machine::{DapContext, ThreadInfo, VariableInfoData};
+use crate::dap_states::{
+    breakpoints::{BreakpointInfo, DataBreakpointInfo, FunctionBreakpointInfo},
+    console::ConsoleLine,
+    dap_state_machine::{
+        DapContext, ExceptionInfo, RequestFailure, StepInTargetInfo, ThreadInfo, VariableInfoData,
+    },
+};
+
+// older lines of a chatty build are dropped, the end of it is what matters
+const MAX_BUILD_OUTPUT_LINES: usize = 1000;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct HeapVariableInfo {
@@ -20,8 +29,52 @@ pub struct HeapVariableInfo {
 #[derive(Default, Debug, Clone, Serialize)]
 pub struct VisualizationState {
     threads: Vec<ThreadInfo>,
+    /// id of the thread whose current line is shown
+    active_thread: Option<i64>,
     heap_variables: Vec<HeapVariableInfo>,
     current_source_file: Option<(String, String, i64)>,
+    request_failures: Vec<RequestFailure>,
+    build_output: Vec<String>,
+    breakpoints: Vec<BreakpointInfo>,
+    function_breakpoints: Vec<FunctionBreakpointInfo>,
+    data_breakpoints: Vec<DataBreakpointInfo>,
+    running: bool,
+    exception: Option<ExceptionInfo>,
+    step_in_targets: Vec<StepInTargetInfo>,
+    /// user requests waiting to be applied once the debug adapter is done with the current one
+    queued_user_requests: usize,
+    exit_code: Option<i64>,
+    /// the debug session is over, this is the last state sent
+    terminated: bool,
+    /// how many times the program stopped so far, console lines printed on the way to the
+    /// current stop have this step
+    step: usize,
+    console: Vec<ConsoleLine>,
+}
+
+impl VisualizationState {
+    /// Adds a line printed by the pre-launch build, shown while the build is still running.
+    pub fn push_build_output(&mut self, line: String) {
+        if self.build_output.len() == MAX_BUILD_OUTPUT_LINES {
+            self.build_output.remove(0);
+        }
+
+        self.build_output.push(line);
+    }
+}
+
+impl VisualizationState {
+    /// Whether this is the last state of the debug session.
+    pub fn is_final(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn with_queued_user_requests(self, queued_user_requests: usize) -> Self {
+        VisualizationState {
+            queued_user_requests,
+            ..self
+        }
+    }
 }
 
 impl From<&DapContext> for VisualizationState {
@@ -32,8 +85,22 @@ impl From<&DapContext> for VisualizationState {
                 .as_ref()
                 .map(|state| state.threads.clone())
                 .unwrap_or_default(),
+            active_thread: context.active_thread,
             heap_variables: Vec::new(),
             current_source_file: None,
+            request_failures: context.request_failures.clone(),
+            build_output: Vec::new(),
+            breakpoints: context.breakpoints.iter().cloned().collect(),
+            function_breakpoints: context.breakpoints.functions().to_vec(),
+            data_breakpoints: context.breakpoints.data().to_vec(),
+            running: context.running,
+            exception: context.exception.clone(),
+            step_in_targets: context.step_in_targets.clone(),
+            queued_user_requests: 0,
+            exit_code: context.exit_code,
+            terminated: context.terminated,
+            step: context.step,
+            console: context.console.lines().cloned().collect(),
         };
 
         let mut heap_variables = BTreeMap::<i64, (VariableInfoData, Vec<VariableInfoData>)>::new();
@@ -110,11 +177,8 @@ impl From<&DapContext> for VisualizationState {
                 })
                 .flatten();
 
-            if let Some(stack_frames) = current_stack_frames {
-                let stack_frame = stack_frames
-                    .last()
-                    .expect("thread must have one stack frame");
-
+            // stack frames were reversed above, so the current one is last
+            if let Some(stack_frame) = current_stack_frames.and_then(|frames| frames.last()) {
                 let file_name = stack_frame.file.clone();
                 let file_contents = context
                     .source_files
@@ -137,9 +201,11 @@ mod test {
 
     use crate::{
         dap_states::{
+            breakpoints::Breakpoints,
+            console::Console,
             dap_state_machine::{
-                DapContext, ProgramState, ScopeInfo, StackFrameInfo, ThreadInfo, VariableInfo,
-                VariableInfoData, VariableResolver,
+                DapContext, DebugTarget, ProgramState, ScopeInfo, StackFrameInfo, ThreadInfo,
+                VariableInfo, VariableInfoData, VariableResolver,
             },
             visualization_state::VisualizationState,
         },
@@ -166,10 +232,12 @@ mod test {
                 "/Users/thekatze/Development/dapviz/playground/csharp/Program.cs".to_string(),
                 "".to_string(),
             )]),
-            executable_path:
-                "/Users/thekatze/Development/dapviz/playground/csharp/bin/Debug/net10.0/csharp.dll"
-                    .into(),
+            target: DebugTarget::launch(
+                "/Users/thekatze/Development/dapviz/playground/csharp/bin/Debug/net10.0/csharp.dll",
+            ),
+            capabilities: None,
             active_thread: Some(12655656),
+            all_threads_stopped: true,
             program_state: Some(ProgramState {
                 threads: vec![ThreadInfo {
                     id: 12655656,
@@ -325,6 +393,15 @@ mod test {
                 }],
             }),
             variable_resolver: VariableResolver::default(),
+            request_failures: Vec::new(),
+            breakpoints: Breakpoints::default(),
+            running: false,
+            exception: None,
+            step_in_targets: Vec::new(),
+            exit_code: None,
+            terminated: false,
+            step: 0,
+            console: Console::default(),
         }
     }
 }
@@ -0,0 +1,203 @@
+use std::{process::Stdio, time::Duration};
+
+use anyhow::Context as _;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    process::{Child, Command},
+};
+
+use crate::debug_adapters::DebugAdapter;
+
+pub type DapReader = Box<dyn AsyncRead + Send + Unpin>;
+pub type DapWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+// how long a debug adapter spawned in server mode gets to start listening
+const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const SERVER_CONNECT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How dapviz talks to the debug adapter.
+#[derive(Debug, Clone)]
+pub enum DapTransport {
+    /// spawns the debug adapter and talks to it over its stdin and stdout
+    Stdio { debugger_path: String },
+    /// connects to a debug adapter listening on `address`, after spawning it in server mode if
+    /// `spawn_debugger` is set
+    Tcp {
+        address: String,
+        spawn_debugger: Option<String>,
+    },
+}
+
+/// An open connection to a debug adapter.
+pub struct DapConnection {
+    pub reader: DapReader,
+    pub writer: DapWriter,
+    /// the debug adapter process, if we spawned it
+    pub process: Option<Child>,
+}
+
+impl DapTransport {
+    pub async fn connect(&self, debug_adapter: &DebugAdapter) -> anyhow::Result<DapConnection> {
+        match self {
+            DapTransport::Stdio { debugger_path } => connect_stdio(debug_adapter, debugger_path),
+            DapTransport::Tcp {
+                address,
+                spawn_debugger: None,
+            } => {
+                let stream = TcpStream::connect(address)
+                    .await
+                    .with_context(|| format!("could not connect to debug adapter at {address}"))?;
+
+                Ok(tcp_connection(stream, None))
+            }
+            DapTransport::Tcp {
+                address,
+                spawn_debugger: Some(debugger_path),
+            } => connect_server(debug_adapter, debugger_path, address).await,
+        }
+    }
+}
+
+fn connect_stdio(
+    debug_adapter: &DebugAdapter,
+    debugger_path: &str,
+) -> anyhow::Result<DapConnection> {
+    let mut child = Command::new(debugger_path)
+        .args(debug_adapter.stdio_arguments())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        // forward error logs of debug adapter
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdin = child
+        .stdin
+        .take()
+        .context("child process did not have a stdin handle")?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("child process did not have a stdout handle")?;
+
+    Ok(DapConnection {
+        reader: Box::new(stdout),
+        writer: Box::new(stdin),
+        process: Some(child),
+    })
+}
+
+async fn connect_server(
+    debug_adapter: &DebugAdapter,
+    debugger_path: &str,
+    address: &str,
+) -> anyhow::Result<DapConnection> {
+    let port = address
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .with_context(|| format!("debug adapter address {address} has no port"))?;
+
+    // the adapter doesn't talk dap over stdio in server mode, so its output can go to our log
+    let mut child = Command::new(debugger_path)
+        .args(debug_adapter.server_arguments(port))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let startup_deadline = tokio::time::Instant::now() + SERVER_STARTUP_TIMEOUT;
+
+    // the adapter needs a moment until it listens, keep trying until then
+    loop {
+        match TcpStream::connect(address).await {
+            Ok(stream) => return Ok(tcp_connection(stream, Some(child))),
+            Err(err) => {
+                if let Some(status) = child.try_wait()? {
+                    anyhow::bail!("debug adapter exited before accepting connections: {status}");
+                }
+
+                if tokio::time::Instant::now() >= startup_deadline {
+                    return Err(err).with_context(|| {
+                        format!("debug adapter did not start listening on {address}")
+                    });
+                }
+
+                tokio::time::sleep(SERVER_CONNECT_INTERVAL).await;
+            }
+        }
+    }
+}
+
+fn tcp_connection(stream: TcpStream, process: Option<Child>) -> DapConnection {
+    // dap messages are small and latency matters more than throughput
+    if let Err(err) = stream.set_nodelay(true) {
+        tracing::warn!("Could not disable nagle's algorithm: {err}");
+    }
+
+    let (reader, writer) = stream.into_split();
+
+    DapConnection {
+        reader: Box::new(reader),
+        writer: Box::new(writer),
+        process,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpListener,
+    };
+
+    use super::DapTransport;
+    use crate::{dap_codec::DapCodec, debug_adapters::DebugAdapter};
+
+    #[tokio::test]
+    async fn connects_to_listening_adapter() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let transport = DapTransport::Tcp {
+            address: listener.local_addr().unwrap().to_string(),
+            spawn_debugger: None,
+        };
+
+        let (connection, accepted) = tokio::join!(
+            transport.connect(&DebugAdapter::NetCoreDbg),
+            listener.accept()
+        );
+        let mut connection = connection.unwrap();
+        let (mut adapter, _) = accepted.unwrap();
+
+        assert!(connection.process.is_none());
+
+        let mut frame = BytesMut::new();
+        DapCodec.encode_frame(b"{}", &mut frame);
+        connection.writer.write_all(&frame).await.unwrap();
+
+        let mut received = vec![0; frame.len()];
+        adapter.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, frame);
+
+        adapter.write_all(&frame).await.unwrap();
+
+        let mut received = vec![0; frame.len()];
+        connection.reader.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, frame);
+    }
+
+    #[tokio::test]
+    async fn fails_without_listening_adapter() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let transport = DapTransport::Tcp {
+            address,
+            spawn_debugger: None,
+        };
+
+        assert!(transport.connect(&DebugAdapter::NetCoreDbg).await.is_err());
+    }
+}
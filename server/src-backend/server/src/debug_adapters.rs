@@ -11,6 +11,31 @@ pub enum DebugAdapter {
     NetCoreDbg,
 }
 
+impl DebugAdapter {
+    /// The `adapterID` sent with the `initialize` request.
+    pub fn adapter_id(&self) -> &'static str {
+        match self {
+            DebugAdapter::NetCoreDbg => "coreclr",
+        }
+    }
+
+    /// Arguments that make the debug adapter talk dap over stdin and stdout.
+    pub fn stdio_arguments(&self) -> Vec<String> {
+        match self {
+            DebugAdapter::NetCoreDbg => vec!["--interpreter=vscode".into()],
+        }
+    }
+
+    /// Arguments that make the debug adapter accept a dap connection on `port`.
+    pub fn server_arguments(&self, port: u16) -> Vec<String> {
+        match self {
+            DebugAdapter::NetCoreDbg => {
+                vec!["--interpreter=vscode".into(), format!("--server={port}")]
+            }
+        }
+    }
+}
+
 #[async_trait]
 pub(crate) trait DebugAdapterFunctions {
     async fn download(&self) -> anyhow::Result<PathBuf>;
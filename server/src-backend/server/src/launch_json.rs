@@ -0,0 +1,285 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// A configuration of a `.vscode/launch.json`, with variables already substituted.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchConfiguration {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub request: Option<String>,
+    pub program: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub stop_at_entry: Option<bool>,
+    /// every other field, forwarded to the debug adapter unchanged
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+    /// folder containing the `.vscode` folder this configuration was read from
+    #[serde(skip)]
+    pub workspace_folder: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct LaunchJson {
+    configurations: Vec<Value>,
+}
+
+impl LaunchConfiguration {
+    /// Loads the configuration called `name` from the `.vscode/launch.json` of the closest
+    /// workspace containing `directory`.
+    pub fn find(directory: &Path, name: &str) -> anyhow::Result<Self> {
+        let workspace_folder = directory
+            .ancestors()
+            .find(|folder| launch_json_path(folder).is_file())
+            .with_context(|| {
+                format!(
+                    "no .vscode/launch.json found in {} or any parent directory",
+                    directory.display()
+                )
+            })?;
+
+        let path = launch_json_path(workspace_folder);
+        let launch_json = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        LaunchConfiguration::parse(&launch_json, workspace_folder, name)
+            .with_context(|| format!("invalid launch configuration in {}", path.display()))
+    }
+
+    pub fn parse(launch_json: &str, workspace_folder: &Path, name: &str) -> anyhow::Result<Self> {
+        let launch_json: LaunchJson = serde_json::from_str(&strip_jsonc(launch_json))?;
+
+        let names = || {
+            launch_json
+                .configurations
+                .iter()
+                .filter_map(|configuration| configuration["name"].as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut configuration = launch_json
+            .configurations
+            .iter()
+            .find(|configuration| configuration["name"] == name)
+            .cloned()
+            .with_context(|| format!("no configuration named {name:?}, found: {}", names()))?;
+
+        substitute_variables(&mut configuration, workspace_folder);
+
+        Ok(LaunchConfiguration {
+            workspace_folder: workspace_folder.to_owned(),
+            ..serde_json::from_value(configuration)?
+        })
+    }
+}
+
+fn launch_json_path(workspace_folder: &Path) -> PathBuf {
+    workspace_folder.join(".vscode").join("launch.json")
+}
+
+/// Replaces the predefined variables vscode supports in launch configurations in every string.
+pub(crate) fn substitute_variables(value: &mut Value, workspace_folder: &Path) {
+    match value {
+        Value::String(string) if string.contains("${") => {
+            *string = substitute_string(string, workspace_folder);
+        }
+        Value::Array(values) => {
+            for value in values {
+                substitute_variables(value, workspace_folder);
+            }
+        }
+        Value::Object(fields) => {
+            for value in fields.values_mut() {
+                substitute_variables(value, workspace_folder);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn substitute_string(string: &str, workspace_folder: &Path) -> String {
+    let mut substituted = String::with_capacity(string.len());
+    let mut rest = string;
+
+    while let Some(start) = rest.find("${") {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+
+        let variable = &rest[start + 2..start + length];
+        let replacement = match variable {
+            "workspaceFolder" | "workspaceRoot" => Some(workspace_folder.display().to_string()),
+            "workspaceFolderBasename" => workspace_folder
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            "pathSeparator" | "/" => Some(std::path::MAIN_SEPARATOR.to_string()),
+            _ => variable
+                .strip_prefix("env:")
+                .map(|name| std::env::var(name).unwrap_or_default()),
+        };
+
+        substituted.push_str(&rest[..start]);
+        match replacement {
+            Some(replacement) => substituted.push_str(&replacement),
+            None => {
+                tracing::warn!("Unsupported variable in launch configuration: ${{{variable}}}");
+                substituted.push_str(&rest[start..=start + length]);
+            }
+        }
+
+        rest = &rest[start + length + 1..];
+    }
+
+    substituted.push_str(rest);
+    substituted
+}
+
+/// Turns json with comments and trailing commas, as written by vscode, into plain json.
+pub(crate) fn strip_jsonc(source: &str) -> String {
+    let mut json = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => {
+                json.push(char);
+
+                while let Some(char) = chars.next() {
+                    json.push(char);
+
+                    match char {
+                        '\\' => json.extend(chars.next()),
+                        '"' => break,
+                        _ => (),
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&char| char != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                let mut previous = None;
+                for char in chars.by_ref() {
+                    if previous == Some('*') && char == '/' {
+                        break;
+                    }
+
+                    previous = Some(char);
+                }
+
+                // a comment still separates tokens
+                json.push(' ');
+            }
+            ']' | '}' => {
+                let content_length = json.trim_end().len();
+                if json[..content_length].ends_with(',') {
+                    json.remove(content_length - 1);
+                }
+
+                json.push(char);
+            }
+            _ => json.push(char),
+        }
+    }
+
+    json
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{LaunchConfiguration, strip_jsonc};
+
+    const LAUNCH_JSON: &str = r#"{
+        // Use IntelliSense to learn about possible attributes.
+        "version": "0.2.0",
+        "configurations": [
+            {
+                "name": ".NET Core Launch (console)",
+                "type": "coreclr",
+                "request": "launch",
+                "preLaunchTask": "build",
+                /* built by the task above */
+                "program": "${workspaceFolder}/bin/Debug/net8.0/Exercise.dll",
+                "args": ["--input", "${workspaceFolder}/input.txt",],
+                "cwd": "${workspaceFolder}",
+                "env": { "GREETING": "hello // not a comment" },
+                "stopAtEntry": false,
+                "justMyCode": false,
+                "console": "internalConsole",
+            },
+            {
+                "name": "Attach",
+                "type": "coreclr",
+                "request": "attach",
+            },
+        ],
+    }"#;
+
+    #[test]
+    fn strips_comments_and_trailing_commas() {
+        let stripped = strip_jsonc(
+            r#"{ "a": "http://x", /* b */ "c": [1, 2,], // d
+        }"#,
+        );
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&stripped).unwrap(),
+            json!({ "a": "http://x", "c": [1, 2] })
+        );
+    }
+
+    #[test]
+    fn parses_configuration_by_name() {
+        let configuration = LaunchConfiguration::parse(
+            LAUNCH_JSON,
+            Path::new("/home/student/exercise"),
+            ".NET Core Launch (console)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            configuration.program.as_deref(),
+            Some("/home/student/exercise/bin/Debug/net8.0/Exercise.dll")
+        );
+        assert_eq!(
+            configuration.args,
+            ["--input", "/home/student/exercise/input.txt"]
+        );
+        assert_eq!(configuration.cwd.as_deref(), Some("/home/student/exercise"));
+        assert_eq!(configuration.env["GREETING"], "hello // not a comment");
+        assert_eq!(configuration.stop_at_entry, Some(false));
+
+        // unknown fields are kept for the adapter
+        assert_eq!(configuration.other["justMyCode"], json!(false));
+        assert_eq!(configuration.other["console"], json!("internalConsole"));
+        assert_eq!(configuration.other["preLaunchTask"], json!("build"));
+    }
+
+    #[test]
+    fn lists_available_configurations_if_name_is_unknown() {
+        let err = LaunchConfiguration::parse(LAUNCH_JSON, Path::new("/"), "Missing").unwrap_err();
+
+        assert!(
+            err.to_string()
+                .contains(".NET Core Launch (console), Attach")
+        );
+    }
+}
@@ -1,32 +1,52 @@
+use std::future::Future;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Context as _;
 use clap::Args;
 use clap::Parser as _;
 use clap::Subcommand;
 
+use build_task::BuildTask;
 use clap::ValueEnum;
 use dap_client::DapClient;
 use dap_client::DapLaunchInfo;
+use dap_replay::DapReplay;
+use dap_states::dap_state_machine::DapStateMachine;
+use dap_states::dap_state_machine::DebugTarget;
+use dap_transport::DapTransport;
 use debug_adapters::DebugAdapter;
 use debug_adapters::DebugAdapterFunctions;
+use launch_json::LaunchConfiguration;
+use serde_json::Value;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
+use user_request::FunctionBreakpoint;
 use user_request::UserRequest;
 use webserver::Webserver;
 
 use crate::dap_states::visualization_state::VisualizationState;
 
+pub mod build_task;
 pub mod dap_client;
+pub mod dap_codec;
+pub mod dap_recorder;
+pub mod dap_replay;
 pub mod dap_states;
+pub mod dap_transport;
 pub mod debug_adapters;
+pub mod launch_json;
+#[cfg(test)]
+mod mock_adapter;
 pub mod user_request;
 pub mod webserver;
 
+// how to reach the debug adapter and set up the session, shared by every command that starts a debug
+// session
 #[derive(Args)]
-pub struct LaunchInfo {
+pub struct AdapterInfo {
     #[arg(
         short,
         long,
@@ -41,9 +61,47 @@ pub struct LaunchInfo {
     )]
     debugger_path: Option<String>,
 
-    #[arg(help = "the program to debug")]
-    executable_path: String,
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["debugger_path", "server_port"],
+        help = "connect to a debug adapter that is already listening on HOST:PORT instead of spawning one"
+    )]
+    connect: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        help = "spawn the debug adapter in server mode listening on PORT and connect to it, instead of talking over stdio"
+    )]
+    server_port: Option<u16>,
+
+    #[arg(
+        long,
+        default_value = "10",
+        value_parser = parse_request_timeout,
+        help = "seconds to wait for the debug adapter to answer a request before giving up on it"
+    )]
+    request_timeout: Duration,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "write every message exchanged with the debug adapter to FILE as json lines"
+    )]
+    record_dap: Option<PathBuf>,
+
+    #[arg(
+        long = "break-function",
+        value_name = "NAME[:CONDITION]",
+        value_parser = parse_function_breakpoint,
+        help = "stop whenever the function NAME is called, optionally only if CONDITION is true, can be repeated"
+    )]
+    break_function: Vec<FunctionBreakpoint>,
+}
 
+#[derive(Args)]
+pub struct WebserverInfo {
     #[arg(
         short,
         long,
@@ -54,41 +112,285 @@ pub struct LaunchInfo {
 
     #[arg(short, long, default_value_t = 80, help = "port to listen on")]
     port: u16,
+
+    #[arg(
+        long,
+        help = "keep serving the final state after the debug session ended instead of exiting, until stopped with Ctrl+C"
+    )]
+    keep_serving: bool,
+
+    #[arg(
+        long,
+        help = "end the debug session and exit once the last client disconnected, e.g. when the editor is closed"
+    )]
+    exit_when_disconnected: bool,
+}
+
+#[derive(Args)]
+pub struct LaunchInfo {
+    #[command(flatten)]
+    adapter: AdapterInfo,
+
+    #[arg(
+        required_unless_present = "config",
+        help = "the program to debug, defaults to the program of --config"
+    )]
+    executable_path: Option<String>,
+
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        help = "arguments passed to the program"
+    )]
+    args: Vec<String>,
+
+    #[arg(long, help = "working directory of the program")]
+    cwd: Option<PathBuf>,
+
+    #[arg(
+        long = "env",
+        value_name = "KEY=VALUE",
+        value_parser = parse_environment_variable,
+        help = "set an environment variable for the program, can be repeated"
+    )]
+    env: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "launch the configuration called NAME of the closest .vscode/launch.json, other arguments override its values"
+    )]
+    config: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "shell command building the program before it is launched, replaces the preLaunchTask of --config"
+    )]
+    build_cmd: Option<String>,
+
+    #[command(flatten)]
+    webserver: WebserverInfo,
+}
+
+fn parse_environment_variable(variable: &str) -> anyhow::Result<(String, String)> {
+    let (key, value) = variable
+        .split_once('=')
+        .context("environment variable must be given as KEY=VALUE")?;
+
+    anyhow::ensure!(
+        !key.is_empty(),
+        "environment variable name must not be empty"
+    );
+
+    Ok((key.into(), value.into()))
+}
+
+fn parse_request_timeout(seconds: &str) -> anyhow::Result<Duration> {
+    let seconds: f32 = seconds
+        .parse()
+        .context("request timeout must be a number of seconds")?;
+
+    // every request would time out right away otherwise
+    anyhow::ensure!(seconds > 0.0, "request timeout must be positive");
+
+    Duration::try_from_secs_f32(seconds).context("request timeout is too long")
+}
+
+fn parse_function_breakpoint(breakpoint: &str) -> anyhow::Result<FunctionBreakpoint> {
+    let (name, condition) = match breakpoint.split_once(':') {
+        Some((name, condition)) => (name, Some(condition.into())),
+        None => (breakpoint, None),
+    };
+
+    anyhow::ensure!(!name.is_empty(), "function name must not be empty");
+
+    Ok(FunctionBreakpoint {
+        name: name.into(),
+        condition,
+    })
+}
+
+#[derive(Args)]
+pub struct AttachInfo {
+    #[command(flatten)]
+    adapter: AdapterInfo,
+
+    #[arg(long, help = "id of the running process to debug")]
+    pid: u32,
+
+    #[command(flatten)]
+    webserver: WebserverInfo,
+}
+
+impl AdapterInfo {
+    fn dap_launch_info(&self, target: DebugTarget) -> anyhow::Result<DapLaunchInfo> {
+        let debugger_path = || -> anyhow::Result<String> {
+            let full_debugger_path = self
+                .debugger_path
+                .as_ref()
+                .map_or_else(|| self.debug_adapter.get_executable_path(), |path| PathBuf::from_str(path).ok())
+                .context("debug adapter not found. install the requested debug adapter with 'dapviz install <ADAPTER_NAME>' or pass '--debugger-path <PATH>'")?
+                .canonicalize()
+                .context("debugger path does not exist")?;
+
+            Ok(dunce::simplified(&full_debugger_path)
+                .to_str()
+                .context("debugger path should be valid utf-8")?
+                .into())
+        };
+
+        let transport = match (&self.connect, self.server_port) {
+            (Some(address), _) => DapTransport::Tcp {
+                address: address.clone(),
+                spawn_debugger: None,
+            },
+            (None, Some(port)) => DapTransport::Tcp {
+                address: format!("127.0.0.1:{port}"),
+                spawn_debugger: Some(debugger_path()?),
+            },
+            (None, None) => DapTransport::Stdio {
+                debugger_path: debugger_path()?,
+            },
+        };
+
+        Ok(DapLaunchInfo {
+            target,
+            build_task: None,
+            function_breakpoints: self.break_function.clone(),
+            transport,
+            debug_adapter: self.debug_adapter.clone(),
+            request_timeout: self.request_timeout,
+            record_dap: self.record_dap.clone(),
+        })
+    }
 }
 
 impl TryFrom<&LaunchInfo> for DapLaunchInfo {
     type Error = anyhow::Error;
 
     fn try_from(value: &LaunchInfo) -> Result<Self, Self::Error> {
-        let full_executable_path = Path::new(&value.executable_path)
-            .canonicalize()
-            .context("executable path does not exist")?;
+        let mut configuration = match &value.config {
+            Some(name) => {
+                let configuration = LaunchConfiguration::find(&std::env::current_dir()?, name)?;
+
+                anyhow::ensure!(
+                    configuration
+                        .request
+                        .as_deref()
+                        .is_none_or(|request| request == "launch"),
+                    "configuration {name:?} is not a launch configuration, use 'dapviz attach' instead"
+                );
+
+                if let Some(type_) = &configuration.type_
+                    && type_ != value.adapter.debug_adapter.adapter_id()
+                {
+                    tracing::warn!(
+                        "Configuration {name:?} is meant for debug adapter {type_:?}, launching it with {:?} anyway",
+                        value.adapter.debug_adapter
+                    );
+                }
 
-        let full_debugger_path = value
-            .debugger_path
+                configuration
+            }
+            None => LaunchConfiguration::default(),
+        };
+
+        let build_task = match (
+            &value.build_cmd,
+            configuration.other.remove("preLaunchTask"),
+        ) {
+            (Some(command_line), _) => Some(BuildTask::shell(command_line)),
+            (None, Some(Value::String(label))) => {
+                Some(BuildTask::find(&configuration.workspace_folder, &label)?)
+            }
+            (None, Some(task)) => anyhow::bail!("preLaunchTask must be a task label, got {task}"),
+            (None, None) => None,
+        };
+
+        let executable_path = value
+            .executable_path
             .as_ref()
-            .map_or_else(|| value.debug_adapter.get_executable_path(), |path| PathBuf::from_str(path).ok())
-            .context("debug adapter not found. install the requested debug adapter with 'dapviz install <ADAPTER_NAME>' or pass '--debugger-path <PATH>'")?
-            .canonicalize()
-            .context("debugger path does not exist")?;
+            .or(configuration.program.as_ref())
+            .context(
+                "no program to debug, pass it as argument or set it in the launch configuration",
+            )?;
 
-        Ok(DapLaunchInfo {
+        let full_executable_path = match build_task {
+            // the build might be what creates the program
+            Some(_) => std::path::absolute(executable_path)?,
+            None => Path::new(executable_path)
+                .canonicalize()
+                .context("executable path does not exist")?,
+        };
+
+        let cwd = value
+            .cwd
+            .clone()
+            .or(configuration.cwd.map(PathBuf::from))
+            .map(|cwd| -> anyhow::Result<String> {
+                let full_cwd = cwd
+                    .canonicalize()
+                    .context("working directory does not exist")?;
+
+                Ok(dunce::simplified(&full_cwd)
+                    .to_str()
+                    .context("working directory should be valid utf-8")?
+                    .into())
+            })
+            .transpose()?;
+
+        let args = match value.args.is_empty() {
+            true => configuration.args,
+            false => value.args.clone(),
+        };
+
+        let mut env = configuration.env;
+        env.extend(value.env.iter().cloned());
+
+        let target = DebugTarget::Launch {
             executable_path: dunce::simplified(&full_executable_path)
                 .to_str()
                 .context("executable path should be valid utf-8")?
                 .into(),
-            debugger_path: dunce::simplified(&full_debugger_path)
-                .to_str()
-                .context("debugger path should be valid utf-8")?
-                .into(),
-            debug_adapter: value.debug_adapter.clone(),
+            args,
+            cwd,
+            env,
+            stop_at_entry: configuration.stop_at_entry.unwrap_or(true),
+            extra_arguments: configuration.other,
+        };
+
+        Ok(DapLaunchInfo {
+            build_task,
+            ..value.adapter.dap_launch_info(target)?
+        })
+    }
+}
+
+impl TryFrom<&AttachInfo> for DapLaunchInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &AttachInfo) -> Result<Self, Self::Error> {
+        value.adapter.dap_launch_info(DebugTarget::Attach {
+            process_id: value.pid,
         })
     }
 }
 
+#[derive(Args)]
+pub struct ReplayInfo {
+    #[arg(help = "dap trace recorded with 'dapviz launch --record-dap'")]
+    trace: PathBuf,
+
+    #[command(flatten)]
+    webserver: WebserverInfo,
+}
+
 #[derive(Subcommand)]
 enum Command {
     Launch(LaunchInfo),
+    Attach(AttachInfo),
+    ReplayDap(ReplayInfo),
     Install { debug_adapter: DebugAdapter },
     ListDebugAdapters,
 }
@@ -99,6 +401,138 @@ struct Cli {
     command: Command,
 }
 
+// how long connected clients get to receive the final state before exiting anyway
+const CLIENT_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// how long the last client gets to come back, e.g. after reloading the page
+const CLIENT_RECONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Runs the dap client created by `run_dap_client` next to the webserver hosting the visualization.
+/// Once the debug session is over, clients are told so before exiting.
+async fn serve<F>(
+    webserver_info: WebserverInfo,
+    run_dap_client: impl FnOnce(DapClient) -> F,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    let (visualization_state_sender, visualization_state_receiver) =
+        tokio::sync::watch::channel::<VisualizationState>(VisualizationState::default());
+
+    let (user_request_sender, user_request_receiver) =
+        tokio::sync::broadcast::channel::<UserRequest>(64);
+
+    let (shutdown_sender, shutdown_receiver) = tokio::sync::watch::channel(false);
+
+    let webserver = Webserver::new(visualization_state_receiver, user_request_sender)
+        .keep_serving(webserver_info.keep_serving);
+    let mut connected_clients = webserver.connected_clients();
+    let dap_client = DapClient::new(visualization_state_sender, user_request_receiver)
+        .with_shutdown(shutdown_receiver);
+
+    // this hosts the visualization and exposes the editor api
+    let webserver = webserver.serve((webserver_info.address, webserver_info.port));
+    tokio::pin!(webserver);
+
+    let dap_client = run_dap_client(dap_client);
+    tokio::pin!(dap_client);
+
+    let shutdown = shutdown_requested(
+        connected_clients.clone(),
+        webserver_info.exit_when_disconnected,
+    );
+    tokio::pin!(shutdown);
+
+    // the dap client ends the debuggee before returning, so it isn't left behind once the debug
+    // adapter gets killed
+    loop {
+        tokio::select! {
+            ok = &mut dap_client => break ok?,
+            ok = &mut webserver => return ok,
+            _ = &mut shutdown, if !*shutdown_sender.borrow() => {
+                shutdown_sender.send_replace(true);
+            }
+        }
+    }
+
+    if webserver_info.keep_serving && !*shutdown_sender.borrow() {
+        tracing::info!("Still serving the final state of the debug session, stop with Ctrl+C");
+
+        // Ctrl+C no longer stops us on its own once we listened for it
+        return tokio::select! {
+            ok = &mut webserver => ok,
+            _ = tokio::signal::ctrl_c() => Ok(()),
+        };
+    }
+
+    // clients close their connection once they got the final state
+    let clients_disconnected = connected_clients.wait_for(|count| *count == 0);
+
+    tokio::select! {
+        _ = tokio::time::timeout(CLIENT_DISCONNECT_TIMEOUT, clients_disconnected) => Ok(()),
+        ok = &mut webserver => ok,
+    }
+}
+
+/// Completes on Ctrl+C or SIGTERM, or once the last client disconnected and didn't come back if
+/// `exit_when_disconnected`.
+async fn shutdown_requested(
+    mut connected_clients: tokio::sync::watch::Receiver<usize>,
+    exit_when_disconnected: bool,
+) {
+    let last_client_disconnected = async {
+        if !exit_when_disconnected {
+            return std::future::pending().await;
+        }
+
+        // without the webserver, nobody can disconnect anymore
+        if clients_left(&mut connected_clients).await.is_err() {
+            std::future::pending().await
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received Ctrl+C"),
+        _ = terminate_signal() => tracing::info!("Received SIGTERM"),
+        _ = last_client_disconnected => tracing::info!("Last client disconnected"),
+    }
+}
+
+/// Waits for the last client to disconnect without coming back.
+async fn clients_left(
+    connected_clients: &mut tokio::sync::watch::Receiver<usize>,
+) -> Result<(), tokio::sync::watch::error::RecvError> {
+    loop {
+        // the editor might not have connected yet
+        connected_clients.wait_for(|count| *count > 0).await?;
+        connected_clients.wait_for(|count| *count == 0).await?;
+
+        let reconnected = connected_clients.wait_for(|count| *count > 0);
+        match tokio::time::timeout(CLIENT_RECONNECT_TIMEOUT, reconnected).await {
+            Ok(reconnected) => _ = reconnected?,
+            Err(..) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn terminate_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => _ = terminate.recv().await,
+        Err(err) => {
+            tracing::warn!("Can't listen for SIGTERM: {err}");
+            std::future::pending().await
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() {
+    std::future::pending().await
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt()
@@ -113,23 +547,30 @@ async fn main() -> anyhow::Result<()> {
 
     match cli.command {
         Command::Launch(launch_info) => {
-            let (visualization_state_sender, visualization_state_receiver) =
-                tokio::sync::watch::channel::<VisualizationState>(VisualizationState::default());
-
-            let (user_request_sender, user_request_receiver) =
-                tokio::sync::broadcast::channel::<UserRequest>(64);
-
-            let webserver = Webserver::new(visualization_state_receiver, user_request_sender);
-            let dap_client = DapClient::new(visualization_state_sender, user_request_receiver);
-
             let dap_launch_info = (&launch_info).try_into()?;
 
-            tokio::select! {
-                ok = dap_client.run(dap_launch_info) => ok,
+            serve(launch_info.webserver, |dap_client| {
+                dap_client.run(dap_launch_info)
+            })
+            .await
+        }
+        Command::Attach(attach_info) => {
+            let dap_launch_info = (&attach_info).try_into()?;
 
-                // this hosts the visualization and exposes the editor api
-                ok = webserver.serve((launch_info.address, launch_info.port)) => ok,
-            }
+            serve(attach_info.webserver, |dap_client| {
+                dap_client.run(dap_launch_info)
+            })
+            .await
+        }
+        Command::ReplayDap(replay_info) => {
+            let replay = DapReplay::load(&replay_info.trace)?;
+            let state_machine = DapStateMachine::new(replay.debug_adapter()?, replay.target()?);
+
+            serve(replay_info.webserver, |dap_client| {
+                // the replay answers instantly, a missing answer will never arrive
+                dap_client.run_with_process(replay.start(), state_machine, Duration::from_secs(1))
+            })
+            .await
         }
         Command::Install { debug_adapter } => {
             let installation_path = debug_adapter.download().await?;
@@ -0,0 +1,757 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bytes::BytesMut;
+use dap_types::types::ProtocolMessage;
+use serde_json::{Value, json};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+
+use crate::{dap_client::DapProcess, dap_codec::DapCodec};
+
+// distinct from variables references, so mixing them up fails loudly
+const FIRST_FRAME_ID: i64 = 1000;
+
+/// A variable shown by the mock adapter. Variables with children are structured and can be
+/// expanded with a `variables` request.
+#[derive(Debug, Clone)]
+pub struct MockVariable {
+    pub name: String,
+    pub value: String,
+    pub type_: String,
+    pub children: Vec<MockVariable>,
+}
+
+impl MockVariable {
+    pub fn new(name: &str, value: &str, type_: &str) -> Self {
+        MockVariable {
+            name: name.into(),
+            value: value.into(),
+            type_: type_.into(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn child(mut self, child: MockVariable) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockScope {
+    pub name: String,
+    pub variables: Vec<MockVariable>,
+}
+
+impl MockScope {
+    pub fn new(name: &str) -> Self {
+        MockScope {
+            name: name.into(),
+            variables: Vec::new(),
+        }
+    }
+
+    pub fn variable(mut self, variable: MockVariable) -> Self {
+        self.variables.push(variable);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockFrame {
+    pub function: String,
+    pub file: String,
+    pub line: i64,
+    pub scopes: Vec<MockScope>,
+}
+
+impl MockFrame {
+    pub fn new(function: &str, file: &str, line: i64) -> Self {
+        MockFrame {
+            function: function.into(),
+            file: file.into(),
+            line,
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn scope(mut self, scope: MockScope) -> Self {
+        self.scopes.push(scope);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockThread {
+    pub id: i64,
+    pub name: String,
+    /// innermost frame first, like in a `stackTrace` response
+    pub frames: Vec<MockFrame>,
+}
+
+impl MockThread {
+    pub fn new(id: i64, name: &str) -> Self {
+        MockThread {
+            id,
+            name: name.into(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frame(mut self, frame: MockFrame) -> Self {
+        self.frames.push(frame);
+        self
+    }
+}
+
+/// The state of the debuggee while it is stopped. The first thread is reported as the one that
+/// stopped, unless `stopped_thread` is set.
+#[derive(Debug, Clone)]
+pub struct MockStop {
+    pub reason: String,
+    pub threads: Vec<MockThread>,
+    pub stopped_thread: Option<i64>,
+    /// the debuggee keeps running until it is paused, instead of stopping here by itself
+    pub needs_pause: bool,
+    /// type name and message of the exception thrown here
+    pub exception: Option<(String, String)>,
+    /// labels of the calls that can be stepped into, numbered from 1
+    pub step_in_targets: Vec<String>,
+    /// category and text of the output printed while running to this stop
+    pub output: Vec<(String, String)>,
+}
+
+impl MockStop {
+    pub fn new(reason: &str) -> Self {
+        MockStop {
+            reason: reason.into(),
+            threads: Vec::new(),
+            stopped_thread: None,
+            needs_pause: false,
+            exception: None,
+            step_in_targets: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Stops because of an exception, as reported by `exceptionInfo`.
+    pub fn exception(mut self, type_name: &str, message: &str) -> Self {
+        self.reason = "exception".into();
+        self.exception = Some((type_name.into(), message.into()));
+        self
+    }
+
+    pub fn step_in_target(mut self, label: &str) -> Self {
+        self.step_in_targets.push(label.into());
+        self
+    }
+
+    pub fn output(mut self, category: &str, text: &str) -> Self {
+        self.output.push((category.into(), text.into()));
+        self
+    }
+
+    pub fn stopped_thread(mut self, thread_id: i64) -> Self {
+        self.stopped_thread = Some(thread_id);
+        self
+    }
+
+    pub fn needs_pause(mut self) -> Self {
+        self.needs_pause = true;
+        self
+    }
+
+    pub fn thread(mut self, thread: MockThread) -> Self {
+        self.threads.push(thread);
+        self
+    }
+}
+
+/// What the mock adapter does during a session.
+///
+/// A launched debuggee stops at the first stop once configuration is done, an attached one when
+/// it gets paused. Every `next`, `stepIn`, `stepOut`, `continue`, `goto` or `restartFrame` request
+/// moves it on to the next one. After the last stop, or when asked to `terminate`, the debuggee
+/// exits with `exit_code` and the session terminates.
+///
+/// Breakpoints are verified on the line they are set on, unless it is one of `moved_breakpoints`.
+#[derive(Debug, Clone, Default)]
+pub struct MockScript {
+    pub stops: Vec<MockStop>,
+    pub exit_code: i64,
+    pub moved_breakpoints: HashMap<i64, i64>,
+    /// whether `gotoTargets` and `goto` are supported, every line is a target
+    pub supports_goto: bool,
+    /// commands that never get an answer, like from a hanging adapter
+    pub unanswered: Vec<String>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        MockScript::default()
+    }
+
+    pub fn stop(mut self, stop: MockStop) -> Self {
+        self.stops.push(stop);
+        self
+    }
+
+    /// Verifies breakpoints set on line `from` on line `to` instead, like an adapter does for
+    /// lines without code.
+    pub fn move_breakpoint(mut self, from: i64, to: i64) -> Self {
+        self.moved_breakpoints.insert(from, to);
+        self
+    }
+
+    pub fn supports_goto(mut self) -> Self {
+        self.supports_goto = true;
+        self
+    }
+
+    pub fn never_answer(mut self, command: &str) -> Self {
+        self.unanswered.push(command.into());
+        self
+    }
+}
+
+/// An in-process debug adapter that answers from a [`MockScript`].
+#[derive(Debug, Clone)]
+pub struct MockAdapter {
+    script: MockScript,
+    received: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockAdapter {
+    pub fn new(script: MockScript) -> Self {
+        MockAdapter {
+            script,
+            received: Arc::default(),
+        }
+    }
+
+    /// Every request received so far, as json.
+    pub fn received_requests(&self) -> Vec<Value> {
+        self.received
+            .lock()
+            .expect("mock adapter lock should not be poisoned")
+            .clone()
+    }
+
+    pub fn received_commands(&self) -> Vec<String> {
+        self.received_requests()
+            .iter()
+            .map(|request| request["command"].as_str().unwrap_or_default().to_owned())
+            .collect()
+    }
+
+    /// Starts answering requests in the background and returns the connection to it.
+    pub fn start(&self) -> DapProcess {
+        let (client, adapter) = tokio::io::duplex(64 * 1024);
+        let (adapter_reader, adapter_writer) = tokio::io::split(adapter);
+        let (client_reader, client_writer) = tokio::io::split(client);
+
+        let session = MockSession {
+            script: self.script.clone(),
+            received: self.received.clone(),
+            stop_index: 0,
+            attached: false,
+            started_threads: Vec::new(),
+            next_seq: 1,
+            next_breakpoint_id: 1,
+            data_breakpoint_ids: Vec::new(),
+            frame_scopes: HashMap::new(),
+            variables: HashMap::new(),
+        };
+
+        tokio::spawn(session.serve(adapter_reader, adapter_writer));
+
+        DapProcess::from_io(client_reader, client_writer)
+    }
+}
+
+struct MockSession {
+    script: MockScript,
+    received: Arc<Mutex<Vec<Value>>>,
+    stop_index: usize,
+    /// attached debuggees keep running until they are paused
+    attached: bool,
+    /// threads reported as started, each stop starts and exits threads as needed
+    started_threads: Vec<i64>,
+    next_seq: usize,
+    next_breakpoint_id: i64,
+    /// ids of the data breakpoints set, all reported as hit when stopping for a data breakpoint
+    data_breakpoint_ids: Vec<i64>,
+    /// scopes by frame id
+    frame_scopes: HashMap<i64, Vec<(String, i64)>>,
+    /// variables by variables reference
+    variables: HashMap<i64, Vec<(MockVariable, i64)>>,
+}
+
+impl MockSession {
+    async fn serve(
+        mut self,
+        mut reader: impl AsyncRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+    ) {
+        let codec = DapCodec;
+        let mut read_buffer = BytesMut::new();
+
+        loop {
+            let frame = loop {
+                match codec.decode_frame(&mut read_buffer) {
+                    Ok(Some(frame)) => break frame,
+                    Ok(None) => (),
+                    Err(err) => panic!("mock adapter received invalid message: {err:#}"),
+                }
+
+                match reader.read_buf(&mut read_buffer).await {
+                    Ok(0) | Err(..) => return,
+                    Ok(_) => (),
+                }
+            };
+
+            // kept as json, parsing would drop adapter specific arguments
+            let request: Value =
+                serde_json::from_slice(&frame).expect("mock adapter received invalid json");
+
+            if request["type"] != "request" {
+                continue;
+            }
+
+            self.received
+                .lock()
+                .expect("mock adapter lock should not be poisoned")
+                .push(request.clone());
+
+            if self
+                .script
+                .unanswered
+                .iter()
+                .any(|command| request["command"] == command.as_str())
+            {
+                continue;
+            }
+
+            let mut write_buffer = BytesMut::new();
+            for reply in self.answer(&request) {
+                codec.encode(&reply, &mut write_buffer);
+            }
+
+            if writer.write_all(&write_buffer).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn answer(&mut self, request: &Value) -> Vec<ProtocolMessage> {
+        let request_seq = &request["seq"];
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+
+        let body = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsCancelRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsDataBreakpoints": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsHitConditionalBreakpoints": true,
+                "supportsLogPoints": true,
+                "supportsExceptionInfoRequest": true,
+                "supportsGotoTargetsRequest": self.script.supports_goto,
+                "supportsStepInTargetsRequest": true,
+                "supportsSteppingGranularity": true,
+                "supportsRestartFrame": true,
+                "supportsTerminateRequest": true,
+                "exceptionBreakpointFilters": [
+                    { "filter": "all", "label": "All Exceptions" },
+                    { "filter": "user-unhandled", "label": "User-Unhandled Exceptions" },
+                ],
+            })),
+            "launch" | "disconnect" | "cancel" => Ok(Value::Null),
+            "attach" => {
+                self.attached = true;
+                Ok(Value::Null)
+            }
+            "configurationDone" | "setExceptionBreakpoints" => Ok(Value::Null),
+            "exceptionInfo" => self.exception_info(),
+            "threads" => Ok(self.threads()),
+            "stackTrace" => self.stack_trace(arguments["threadId"].as_i64().unwrap_or_default()),
+            "scopes" => self.scopes(arguments["frameId"].as_i64().unwrap_or_default()),
+            "variables" => {
+                self.variables(arguments["variablesReference"].as_i64().unwrap_or_default())
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "setFunctionBreakpoints" => Ok(self.set_function_breakpoints(arguments)),
+            "dataBreakpointInfo" => Ok(self.data_breakpoint_info(arguments)),
+            "setDataBreakpoints" => Ok(self.set_data_breakpoints(arguments)),
+            "gotoTargets" if self.script.supports_goto => {
+                let line = &arguments["line"];
+                Ok(json!({ "targets": [{ "id": line, "label": "line", "line": line }] }))
+            }
+            "stepInTargets" => Ok(self.step_in_targets()),
+            "next" | "stepIn" | "stepOut" | "pause" | "goto" | "restartFrame" | "terminate" => {
+                Ok(Value::Null)
+            }
+            "continue" => Ok(json!({ "allThreadsContinued": true })),
+            _ => Err(format!("{command} is not supported by the mock adapter")),
+        };
+
+        let response = match body {
+            Ok(Value::Null) => json!({
+                "type": "response",
+                "request_seq": request_seq,
+                "success": true,
+                "command": command,
+            }),
+            Ok(body) => json!({
+                "type": "response",
+                "request_seq": request_seq,
+                "success": true,
+                "command": command,
+                "body": body,
+            }),
+            Err(message) => json!({
+                "type": "response",
+                "request_seq": request_seq,
+                "success": false,
+                "command": command,
+                "message": message,
+            }),
+        };
+
+        let mut replies = vec![self.message(response)];
+
+        match command {
+            "initialize" => replies.push(self.event("initialized", Value::Null)),
+            "configurationDone" if !self.attached => replies.extend(self.stop_or_exit()),
+            "pause" => replies.extend(self.stop_or_exit()),
+            "terminate" => {
+                self.stop_index = self.script.stops.len();
+                replies.extend(self.stop_or_exit());
+            }
+            "next" | "stepIn" | "stepOut" | "continue" | "goto" | "restartFrame" => {
+                self.stop_index += 1;
+
+                if !self.current_stop().is_some_and(|stop| stop.needs_pause) {
+                    replies.extend(self.stop_or_exit());
+                }
+            }
+            _ => (),
+        }
+
+        replies
+    }
+
+    fn current_stop(&self) -> Option<&MockStop> {
+        self.script.stops.get(self.stop_index)
+    }
+
+    fn stop_or_exit(&mut self) -> Vec<ProtocolMessage> {
+        let Some(stop) = self.current_stop().cloned() else {
+            return vec![
+                self.event("exited", json!({ "exitCode": self.script.exit_code })),
+                self.event("terminated", json!({})),
+            ];
+        };
+
+        self.assign_references(&stop);
+
+        let mut replies = Vec::new();
+
+        for (category, text) in &stop.output {
+            let body = json!({ "category": category, "output": text });
+            replies.push(self.event("output", body));
+        }
+
+        let thread_ids: Vec<_> = stop.threads.iter().map(|thread| thread.id).collect();
+        for exited in self.started_threads.clone() {
+            if !thread_ids.contains(&exited) {
+                let body = json!({ "reason": "exited", "threadId": exited });
+                replies.push(self.event("thread", body));
+            }
+        }
+        for started in &thread_ids {
+            if !self.started_threads.contains(started) {
+                let body = json!({ "reason": "started", "threadId": started });
+                replies.push(self.event("thread", body));
+            }
+        }
+        self.started_threads = thread_ids;
+
+        let thread_id = stop
+            .stopped_thread
+            .or(stop.threads.first().map(|thread| thread.id))
+            .unwrap_or_default();
+
+        let mut body = json!({
+            "reason": stop.reason,
+            "threadId": thread_id,
+            "allThreadsStopped": true,
+        });
+
+        if stop.reason == "data breakpoint" {
+            body["hitBreakpointIds"] = json!(self.data_breakpoint_ids);
+        }
+
+        replies.push(self.event("stopped", body));
+        replies
+    }
+
+    /// Numbers frames, scopes and structured variables of a stop, in the order they appear.
+    fn assign_references(&mut self, stop: &MockStop) {
+        self.frame_scopes.clear();
+        self.variables.clear();
+
+        let mut next_reference = 1;
+        let frames = stop.threads.iter().flat_map(|thread| &thread.frames);
+
+        for (frame_id, frame) in (FIRST_FRAME_ID..).zip(frames) {
+            let scopes = frame
+                .scopes
+                .iter()
+                .map(|scope| {
+                    let reference = self.assign_variables(&scope.variables, &mut next_reference);
+                    (scope.name.clone(), reference)
+                })
+                .collect();
+
+            self.frame_scopes.insert(frame_id, scopes);
+        }
+    }
+
+    fn assign_variables(&mut self, variables: &[MockVariable], next_reference: &mut i64) -> i64 {
+        let reference = *next_reference;
+        *next_reference += 1;
+
+        let variables = variables
+            .iter()
+            .map(|variable| {
+                let child_reference = if variable.children.is_empty() {
+                    0
+                } else {
+                    self.assign_variables(&variable.children, next_reference)
+                };
+
+                (variable.clone(), child_reference)
+            })
+            .collect();
+
+        self.variables.insert(reference, variables);
+        reference
+    }
+
+    fn threads(&self) -> Value {
+        let threads: Vec<_> = self
+            .current_stop()
+            .map(|stop| stop.threads.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|thread| json!({ "id": thread.id, "name": thread.name }))
+            .collect();
+
+        json!({ "threads": threads })
+    }
+
+    fn stack_trace(&self, thread_id: i64) -> Result<Value, String> {
+        let stop = self.current_stop().ok_or("debuggee is not stopped")?;
+
+        // frame ids are handed out across all threads in order, see assign_references
+        let mut frame_id = FIRST_FRAME_ID;
+        for thread in &stop.threads {
+            if thread.id != thread_id {
+                frame_id += thread.frames.len() as i64;
+                continue;
+            }
+
+            let frames: Vec<_> = thread
+                .frames
+                .iter()
+                .zip(frame_id..)
+                .map(|(frame, id)| {
+                    json!({
+                        "id": id,
+                        "name": frame.function,
+                        "line": frame.line,
+                        "column": 1,
+                        "source": { "path": frame.file },
+                    })
+                })
+                .collect();
+
+            return Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }));
+        }
+
+        Err(format!("unknown thread {thread_id}"))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        let breakpoints: Vec<_> = requested
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_i64().unwrap_or_default();
+                let id = self.next_breakpoint_id;
+                self.next_breakpoint_id += 1;
+
+                json!({
+                    "id": id,
+                    "verified": true,
+                    "line": self.script.moved_breakpoints.get(&line).unwrap_or(&line),
+                    "source": arguments["source"],
+                })
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_function_breakpoints(&mut self, arguments: &Value) -> Value {
+        let count = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+
+        let breakpoints: Vec<_> = (0..count)
+            .map(|_| {
+                let id = self.next_breakpoint_id;
+                self.next_breakpoint_id += 1;
+
+                json!({ "id": id, "verified": true })
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    /// Data can be watched by the memory reference of the variable, which stays the same across
+    /// stops as long as the script does.
+    fn data_breakpoint_info(&self, arguments: &Value) -> Value {
+        let reference = arguments["variablesReference"].as_i64().unwrap_or_default();
+        let name = arguments["name"].as_str().unwrap_or_default();
+
+        let index = self.variables.get(&reference).and_then(|variables| {
+            variables
+                .iter()
+                .position(|(variable, _)| variable.name == name)
+        });
+
+        match index {
+            Some(index) => json!({
+                "dataId": memory_reference(reference, index),
+                "description": name,
+                "accessTypes": ["write"],
+            }),
+            None => json!({ "dataId": null, "description": format!("{name} is not a variable") }),
+        }
+    }
+
+    fn set_data_breakpoints(&mut self, arguments: &Value) -> Value {
+        let count = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+
+        self.data_breakpoint_ids = (0..count)
+            .map(|_| {
+                let id = self.next_breakpoint_id;
+                self.next_breakpoint_id += 1;
+                id
+            })
+            .collect();
+
+        let breakpoints: Vec<_> = self
+            .data_breakpoint_ids
+            .iter()
+            .map(|id| json!({ "id": id, "verified": true }))
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn step_in_targets(&self) -> Value {
+        let targets: Vec<_> = self
+            .current_stop()
+            .map(|stop| stop.step_in_targets.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .zip(1..)
+            .map(|(label, id)| json!({ "id": id, "label": label }))
+            .collect();
+
+        json!({ "targets": targets })
+    }
+
+    fn exception_info(&self) -> Result<Value, String> {
+        let (type_name, message) = self
+            .current_stop()
+            .and_then(|stop| stop.exception.as_ref())
+            .ok_or("debuggee did not stop at an exception")?;
+
+        Ok(json!({
+            "exceptionId": type_name,
+            "breakMode": "always",
+            "details": {
+                "message": message,
+                "typeName": type_name.rsplit('.').next(),
+                "fullTypeName": type_name,
+                "stackTrace": "   at Program.Add() in /mock/Program.cs:line 10",
+            },
+        }))
+    }
+
+    fn scopes(&self, frame_id: i64) -> Result<Value, String> {
+        let scopes: Vec<_> = self
+            .frame_scopes
+            .get(&frame_id)
+            .ok_or_else(|| format!("unknown frame {frame_id}"))?
+            .iter()
+            .map(|(name, reference)| {
+                json!({ "name": name, "variablesReference": reference, "expensive": false })
+            })
+            .collect();
+
+        Ok(json!({ "scopes": scopes }))
+    }
+
+    fn variables(&self, reference: i64) -> Result<Value, String> {
+        let variables: Vec<_> = self
+            .variables
+            .get(&reference)
+            .ok_or_else(|| format!("unknown variables reference {reference}"))?
+            .iter()
+            .enumerate()
+            .map(|(index, (variable, child_reference))| {
+                json!({
+                    "name": variable.name,
+                    "value": variable.value,
+                    "type": variable.type_,
+                    "variablesReference": child_reference,
+                    "memoryReference": memory_reference(reference, index),
+                })
+            })
+            .collect();
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> ProtocolMessage {
+        let mut event = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            event["body"] = body;
+        }
+
+        self.message(event)
+    }
+
+    fn message(&mut self, mut message: Value) -> ProtocolMessage {
+        message["seq"] = self.next_seq.into();
+        self.next_seq += 1;
+
+        serde_json::from_value(message).expect("mock adapter should only send valid messages")
+    }
+}
+
+fn memory_reference(variables_reference: i64, index: usize) -> String {
+    format!("0x{:08x}{:08x}", variables_reference, index)
+}
@@ -1,11 +1,127 @@
+use anyhow::Context as _;
 use axum::extract::ws::Message;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+/// A line breakpoint as the user wants it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LineBreakpoint {
+    pub file: String,
+    pub line: i64,
+    /// only stop if this expression is true
+    pub condition: Option<String>,
+    /// only stop after this many hits, how it is interpreted is up to the debug adapter
+    pub hit_condition: Option<String>,
+    /// log this message instead of stopping, expressions in `{}` are interpolated
+    pub log_message: Option<String>,
+}
+
+/// A breakpoint stopping whenever a function is called.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FunctionBreakpoint {
+    /// e.g. `Program.Factorial`, how it is resolved is up to the debug adapter
+    pub name: String,
+    /// only stop if this expression is true
+    pub condition: Option<String>,
+}
+
+/// How far a step goes, only sent if the debug adapter supports it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SteppingGranularity {
+    Statement,
+    Line,
+    Instruction,
+}
+
+impl From<SteppingGranularity> for dap_types::types::SteppingGranularity {
+    fn from(value: SteppingGranularity) -> Self {
+        match value {
+            SteppingGranularity::Statement => dap_types::types::SteppingGranularity::Statement,
+            SteppingGranularity::Line => dap_types::types::SteppingGranularity::Line,
+            SteppingGranularity::Instruction => dap_types::types::SteppingGranularity::Instruction,
+        }
+    }
+}
+
+/// Stepping, continuing and pausing is sent as binary messages by the controls, everything else
+/// as json text messages tagged with `request`, e.g.
+/// `{"request":"toggle_breakpoint","file":"/src/Program.cs","line":12}`. Steps with a target or
+/// granularity are sent as json as well, e.g. `{"request":"step_in","thread":1,"target":2}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "request", rename_all = "snake_case")]
 pub enum UserRequest {
-    Step(i64),
-    StepIn(i64),
-    StepOut(i64),
+    Step {
+        thread: i64,
+        granularity: Option<SteppingGranularity>,
+    },
+    /// `target` is the id of one of the step in targets of the visualization state
+    StepIn {
+        thread: i64,
+        target: Option<i64>,
+        granularity: Option<SteppingGranularity>,
+    },
+    StepOut {
+        thread: i64,
+        granularity: Option<SteppingGranularity>,
+    },
+    #[serde(skip)]
+    Continue(i64),
+    #[serde(skip)]
+    Pause(i64),
+    SetBreakpoint(LineBreakpoint),
+    /// clears the breakpoint on `line`, whether the user put it there or the debug adapter
+    /// moved it there
+    ClearBreakpoint {
+        file: String,
+        line: i64,
+    },
+    /// sets the breakpoint if there is none on its line, clears it otherwise
+    ToggleBreakpoint(LineBreakpoint),
+    SetFunctionBreakpoint(FunctionBreakpoint),
+    ClearFunctionBreakpoint {
+        name: String,
+    },
+    /// stops whenever the variable `name` changes, listed in the scope or heap variable whose
+    /// variables reference is `reference`
+    SetDataBreakpoint {
+        reference: i64,
+        name: String,
+    },
+    ClearDataBreakpoint {
+        data_id: String,
+    },
+    /// lets `thread` run until it reaches `line`, e.g. the line the cursor is on in an editor
+    RunToLine {
+        file: String,
+        line: i64,
+        thread: i64,
+    },
+    /// shows the stack and current line of `thread` instead of the one that stopped
+    SelectThread {
+        thread: i64,
+    },
+    /// runs the frame `frame_id` again from its start
+    RestartFrame {
+        frame_id: i64,
+    },
+    /// ends the debuggee and launches it again, keeping line and function breakpoints
+    RestartSession,
+}
+
+impl UserRequest {
+    /// Whether the program moves on, after which anything referring to the current stop, like
+    /// variables references or frame ids, is no longer valid.
+    pub fn moves_program(&self) -> bool {
+        matches!(
+            self,
+            UserRequest::Step { .. }
+                | UserRequest::StepIn { .. }
+                | UserRequest::StepOut { .. }
+                | UserRequest::Continue(..)
+                | UserRequest::RunToLine { .. }
+                | UserRequest::RestartFrame { .. }
+        )
+    }
 }
 
 impl TryFrom<Message> for UserRequest {
@@ -14,7 +130,10 @@ impl TryFrom<Message> for UserRequest {
     fn try_from(value: Message) -> Result<Self, Self::Error> {
         let request_bytes = match value {
             Message::Binary(bytes) => bytes,
-            _ => anyhow::bail!("expected binary websocket message"),
+            Message::Text(text) => {
+                return serde_json::from_str(&text).context("invalid json user request");
+            }
+            _ => anyhow::bail!("expected binary or text websocket message"),
         };
 
         anyhow::ensure!(request_bytes.len() == 9, "Unexpected message length");
@@ -23,9 +142,21 @@ impl TryFrom<Message> for UserRequest {
         let request_id = request_bytes[8];
 
         Ok(match request_id {
-            1 => UserRequest::Step(thread_id),
-            2 => UserRequest::StepIn(thread_id),
-            3 => UserRequest::StepOut(thread_id),
+            1 => UserRequest::Step {
+                thread: thread_id,
+                granularity: None,
+            },
+            2 => UserRequest::StepIn {
+                thread: thread_id,
+                target: None,
+                granularity: None,
+            },
+            3 => UserRequest::StepOut {
+                thread: thread_id,
+                granularity: None,
+            },
+            4 => UserRequest::Continue(thread_id),
+            5 => UserRequest::Pause(thread_id),
             _ => anyhow::bail!("unknown user request id: {}", request_id),
         })
     }
@@ -4,7 +4,7 @@ use axum::{
     Router,
     extract::{
         State, WebSocketUpgrade,
-        ws::{Message, Utf8Bytes, WebSocket},
+        ws::{CloseFrame, Message, Utf8Bytes, WebSocket, close_code},
     },
     http::StatusCode,
     response::IntoResponse,
@@ -17,12 +17,16 @@ use crate::{dap_states::visualization_state::VisualizationState, user_request::U
 pub struct Webserver {
     visualization_state_receiver: tokio::sync::watch::Receiver<VisualizationState>,
     user_request_sender: tokio::sync::broadcast::Sender<UserRequest>,
+    connected_clients: tokio::sync::watch::Sender<usize>,
+    keep_serving: bool,
 }
 
 #[derive(Clone)]
 struct AppState {
     visualization_state: Arc<tokio::sync::watch::Receiver<VisualizationState>>,
     request_sender: Arc<tokio::sync::broadcast::Sender<UserRequest>>,
+    connected_clients: Arc<tokio::sync::watch::Sender<usize>>,
+    keep_serving: bool,
 }
 
 impl Webserver {
@@ -33,16 +37,39 @@ impl Webserver {
         Webserver {
             visualization_state_receiver,
             user_request_sender,
+            connected_clients: tokio::sync::watch::Sender::new(0),
+            keep_serving: false,
         }
     }
 
+    /// Keeps clients connected after the debug session is over, so they can still look at its
+    /// final state. They get disconnected once it is over otherwise.
+    pub fn keep_serving(self, keep_serving: bool) -> Self {
+        Webserver {
+            keep_serving,
+            ..self
+        }
+    }
+
+    /// The number of clients connected to the events websocket.
+    pub fn connected_clients(&self) -> tokio::sync::watch::Receiver<usize> {
+        self.connected_clients.subscribe()
+    }
+
     pub async fn serve(self, address: impl tokio::net::ToSocketAddrs) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(address).await?;
+
+        self.serve_listener(listener).await
+    }
+
+    pub async fn serve_listener(self, listener: TcpListener) -> anyhow::Result<()> {
         let state = AppState {
             visualization_state: Arc::new(self.visualization_state_receiver),
             request_sender: Arc::new(self.user_request_sender),
+            connected_clients: Arc::new(self.connected_clients),
+            keep_serving: self.keep_serving,
         };
 
-        let listener = TcpListener::bind(address).await?;
         let app = build_app().with_state(state);
 
         tracing::info!("Server started on http://{}", listener.local_addr()?);
@@ -66,22 +93,41 @@ async fn initialize_events_websocket(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| {
-        handle_socket(
-            socket,
-            state.visualization_state.deref().clone(),
-            state.request_sender.deref().clone(),
-        )
-    })
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
 }
 
 const READY_MESSAGE: Message = Message::Text(Utf8Bytes::from_static("ready"));
 
-async fn handle_socket(
-    mut socket: WebSocket,
-    mut program_state: tokio::sync::watch::Receiver<VisualizationState>,
-    request_sender: tokio::sync::broadcast::Sender<UserRequest>,
-) {
+/// Counts a client as connected for as long as it is alive.
+struct ConnectedClient(Arc<tokio::sync::watch::Sender<usize>>);
+
+impl ConnectedClient {
+    fn new(connected_clients: Arc<tokio::sync::watch::Sender<usize>>) -> Self {
+        connected_clients.send_modify(|count| *count += 1);
+        ConnectedClient(connected_clients)
+    }
+}
+
+impl Drop for ConnectedClient {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
+
+async fn close(mut socket: WebSocket, code: u16, reason: &'static str) {
+    let frame = CloseFrame {
+        code,
+        reason: Utf8Bytes::from_static(reason),
+    };
+
+    // the client might be gone already, which is just as good
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+async fn handle_socket(mut socket: WebSocket, state: AppState) {
+    let mut program_state = state.visualization_state.deref().clone();
+    let request_sender = state.request_sender.deref().clone();
+
     // only start communication after websocket sends ready message
     let Some(Ok(init_message)) = socket.recv().await else {
         return;
@@ -91,15 +137,28 @@ async fn handle_socket(
         return;
     }
 
+    let _connected_client = ConnectedClient::new(state.connected_clients.clone());
+    let mut dap_client_running = true;
+
     loop {
-        let serialized = serde_json::to_string(program_state.borrow_and_update().deref())
-            .expect("ProgramState must not contain a Map with non-string keys");
+        let (serialized, is_final) = {
+            let program_state = program_state.borrow_and_update();
+            let serialized = serde_json::to_string(program_state.deref())
+                .expect("ProgramState must not contain a Map with non-string keys");
+
+            (serialized, program_state.is_final())
+        };
 
         if socket.send(Message::Text(serialized.into())).await.is_err() {
             // websocket closed
             break;
         }
 
+        if is_final && !state.keep_serving {
+            close(socket, close_code::NORMAL, "debug session ended").await;
+            return;
+        }
+
         tokio::select! {
             received = socket.recv() => {
                 if let Some(message) = received.and_then(|result| result.ok()) {
@@ -112,9 +171,8 @@ async fn handle_socket(
                     }
 
                     match UserRequest::try_from(message) {
-                        Ok(request) => if request_sender.send(request).is_err() {
-                            // dap server closed
-                            break;
+                        Ok(request) => if let Err(err) = request_sender.send(request) {
+                            tracing::debug!("Ignoring {:?}, the debug session is over", err.0);
                         }
                         Err(err) => {
                             tracing::error!("Invalid UserRequest: {err}");
@@ -125,10 +183,15 @@ async fn handle_socket(
                     break;
                 }
             },
-            change_result = program_state.changed() => {
+            change_result = program_state.changed(), if dap_client_running => {
                 if change_result.is_err() {
                     // dap server closed
-                    break;
+                    if !state.keep_serving {
+                        close(socket, close_code::AWAY, "dapviz is shutting down").await;
+                        return;
+                    }
+
+                    dap_client_running = false;
                 }
             }
         }
@@ -1,7 +1,11 @@
-import DapvizProvider, { useDapviz } from "./DapvizProvider";
+import DapvizProvider, {
+  ConsoleLine,
+  DataBreakpoint,
+  ExceptionInfo,
+  useDapviz,
+} from "./DapvizProvider";
 import Visualizer from "./Visualizer";
 import Controls from "./Controls";
-import { useState } from "react";
 import { ThemeProvider } from "./ThemeProvider";
 
 const NoConnectionError = () => (
@@ -13,25 +17,109 @@ const NoConnectionError = () => (
   </div>
 );
 
+const BuildOutput = ({ output }: { output: string[] }) => (
+  <div className="flex flex-col h-full w-full p-4">
+    <h1 className="text-2xl mb-2">Building</h1>
+    <pre className="overflow-auto text-sm">{output.join("\n")}</pre>
+  </div>
+);
+
+const ExceptionBanner = ({ exception }: { exception: ExceptionInfo }) => (
+  <div className="p-4 rounded shadow bg-red-100 dark:bg-red-900 max-h-[33vh] overflow-auto pointer-events-auto">
+    <h2 className="font-bold">{exception.type_name}</h2>
+    {exception.message && <p>{exception.message}</p>}
+    {exception.stack_trace && <pre className="text-xs mt-2">{exception.stack_trace}</pre>}
+  </div>
+);
+
+const DataBreakpointHits = ({ breakpoints }: { breakpoints: DataBreakpoint[] }) => (
+  <div className="absolute top-0 inset-x-0 m-4 p-4 rounded shadow bg-yellow-100 dark:bg-yellow-900">
+    {breakpoints.map(
+      (breakpoint) =>
+        breakpoint.hit && (
+          <p key={breakpoint.data_id}>
+            <span className="font-bold">{breakpoint.description}</span> changed from{" "}
+            <code>{breakpoint.hit.old_value}</code> to <code>{breakpoint.hit.new_value}</code>
+          </p>
+        ),
+    )}
+  </div>
+);
+
+// lines printed on the way to the current stop stand out
+const ProgramConsole = ({ lines, step }: { lines: ConsoleLine[]; step: number }) => (
+  <div className="self-end p-4 w-1/3 max-h-[25vh] rounded shadow bg-neutral-100 dark:bg-neutral-800 overflow-auto pointer-events-auto">
+    <pre className="text-sm">
+      {lines.map((line, index) => (
+        <div
+          key={index}
+          className={[
+            line.category === "stderr" && "text-red-600 dark:text-red-400",
+            line.category === "console" && "italic opacity-75",
+            line.step === step && "font-bold",
+          ]
+            .filter(Boolean)
+            .join(" ")}
+        >
+          {line.text}
+        </div>
+      ))}
+    </pre>
+  </div>
+);
+
+const SessionEnded = ({ exitCode }: { exitCode: number | null }) => (
+  <div className="p-4 rounded shadow bg-neutral-100 dark:bg-neutral-800 pointer-events-auto">
+    <p>
+      Program exited{exitCode !== null && <> with code <code>{exitCode}</code></>}.
+    </p>
+  </div>
+);
+
 const DapvizApp = () => {
   const [visualizationState] = useDapviz();
-  const [currentThread, setCurrentThread] = useState<number>(0);
+  // the thread that stopped or was selected, shown along with its current line
+  const currentThread = Math.max(
+    visualizationState.threads.findIndex(
+      (thread) => thread.id === visualizationState.active_thread,
+    ),
+    0,
+  );
+  const hitDataBreakpoints = visualizationState.data_breakpoints.filter(
+    (breakpoint) => breakpoint.hit,
+  );
 
   return (
     <>
-      <Visualizer
-        thread={visualizationState.threads[currentThread]}
-        heapVariables={visualizationState.heap_variables}
-        currentSourceFile={visualizationState.current_source_file}
-      />
-      <Controls currentThread={currentThread} setCurrentThread={setCurrentThread} />
+      {visualizationState.threads.length > 0 && (
+        <Visualizer
+          thread={visualizationState.threads[currentThread]}
+          heapVariables={visualizationState.heap_variables}
+          currentSourceFile={visualizationState.current_source_file}
+        />
+      )}
+      {!visualizationState.terminated && <Controls currentThread={currentThread} />}
+      {hitDataBreakpoints.length > 0 && <DataBreakpointHits breakpoints={hitDataBreakpoints} />}
+      {/* stacked so the console never covers how the program stopped or ended */}
+      <div className="absolute bottom-0 inset-x-0 m-4 flex flex-col gap-4 pointer-events-none">
+        {visualizationState.console.length > 0 && (
+          <ProgramConsole lines={visualizationState.console} step={visualizationState.step} />
+        )}
+        {visualizationState.exception && (
+          <ExceptionBanner exception={visualizationState.exception} />
+        )}
+        {visualizationState.terminated && <SessionEnded exitCode={visualizationState.exit_code} />}
+      </div>
     </>
   );
 };
 
 const App = () => (
   <ThemeProvider>
-    <DapvizProvider noConnection={<NoConnectionError />}>
+    <DapvizProvider
+      noConnection={<NoConnectionError />}
+      building={(output) => <BuildOutput output={output} />}
+    >
       <DapvizApp />
     </DapvizProvider>
   </ThemeProvider>
@@ -7,19 +7,14 @@ const Button = ({ className, ...props }: ComponentProps<"button">) => (
     className={clsx(
       className,
       "text-sm cursor-pointer bg-white hover:bg-neutral-100 dark:bg-neutral-800 dark:hover:bg-neutral-700 px-3 py-1 font-sans",
+      "disabled:cursor-not-allowed disabled:opacity-50",
     )}
     {...props}
   />
 );
 
-const Controls = ({
-  currentThread,
-  setCurrentThread,
-}: {
-  currentThread: number;
-  setCurrentThread: React.Dispatch<React.SetStateAction<number>>;
-}) => {
-  const [programState, send] = useDapviz();
+const Controls = ({ currentThread }: { currentThread: number }) => {
+  const [programState, send, sendJson] = useDapviz();
 
   const request = (request: DapvizRequest) => () =>
     send(request, programState.threads[currentThread].id);
@@ -29,18 +24,46 @@ const Controls = ({
       <Button className="rounded pointer-events-auto" onClick={() => window.location.reload()}>
         Reload Visualization
       </Button>
+      <Button
+        className="rounded pointer-events-auto"
+        onClick={() => sendJson({ request: "restart_session" })}
+      >
+        Restart Program
+      </Button>
       <div className="flex flex-row gap-px bg-neutral-300 dark:bg-neutral-500 rounded pointer-events-auto shadow">
-        <Button className="rounded-l" onClick={request(DapvizRequest.Step)}>
+        {programState.running ? (
+          <Button className="rounded-l" onClick={request(DapvizRequest.Pause)}>
+            Pause
+          </Button>
+        ) : (
+          <Button className="rounded-l" onClick={request(DapvizRequest.Continue)}>
+            Continue
+          </Button>
+        )}
+        <Button disabled={programState.running} onClick={request(DapvizRequest.Step)}>
           Step Over
         </Button>
-        <Button onClick={request(DapvizRequest.StepIn)}>Step In</Button>
-        <Button className="rounded-r" onClick={request(DapvizRequest.StepOut)}>
+        <Button disabled={programState.running} onClick={request(DapvizRequest.StepIn)}>
+          Step In
+        </Button>
+        <Button
+          className="rounded-r"
+          disabled={programState.running}
+          onClick={request(DapvizRequest.StepOut)}
+        >
           Step Out
         </Button>
       </div>
+      {programState.queued_user_requests > 0 && (
+        <span className="text-sm self-center px-3 py-1 rounded shadow bg-white dark:bg-neutral-800">
+          {programState.queued_user_requests} queued
+        </span>
+      )}
       <select
-        defaultValue={currentThread}
-        onChange={(e) => setCurrentThread(+e.target.value)}
+        value={currentThread}
+        onChange={(e) =>
+          sendJson({ request: "select_thread", thread: programState.threads[+e.target.value].id })
+        }
         className="text-sm pointer-events-auto cursor-pointer bg-white hover:bg-neutral-100 dark:bg-neutral-800 dark:hover:bg-neutral-700 px-3 py-1 rounded shadow"
       >
         {programState.threads.map((thread, i) => (
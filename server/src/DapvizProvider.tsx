@@ -6,6 +6,8 @@ export enum DapvizRequest {
   Step = 1,
   StepIn = 2,
   StepOut = 3,
+  Continue = 4,
+  Pause = 5,
 }
 
 export type Variable = {
@@ -20,6 +22,7 @@ export type Variable = {
 export type HeapVariable = Omit<Variable, "parent"> & { fields: Variable[] };
 
 export type Scope = {
+  variables_reference: number;
   variables: [Variable];
 };
 
@@ -37,15 +40,118 @@ export type ThreadInfo = {
   stack_frames: [StackFrame];
 };
 
+export type RequestFailure = {
+  command: string;
+  message: string;
+};
+
+export type Breakpoint = {
+  file: string;
+  line: number;
+  condition: string | null;
+  hit_condition: string | null;
+  log_message: string | null;
+  verified: boolean;
+  actual_line: number | null;
+  message: string | null;
+};
+
+export type FunctionBreakpoint = {
+  name: string;
+  condition: string | null;
+  verified: boolean;
+  message: string | null;
+};
+
+export type DataBreakpoint = {
+  data_id: string;
+  description: string;
+  name: string;
+  value: string;
+  verified: boolean;
+  message: string | null;
+  hit: { old_value: string; new_value: string } | null;
+};
+
+export type ExceptionInfo = {
+  type_name: string;
+  message: string | null;
+  stack_trace: string | null;
+};
+
+export type ConsoleLine = {
+  category: "stdout" | "stderr" | "console";
+  text: string;
+  // the stop the program was running towards when the line was printed
+  step: number;
+};
+
+export type StepInTarget = {
+  id: number;
+  label: string;
+  line: number | null;
+};
+
+export type SteppingGranularity = "statement" | "line" | "instruction";
+
 export type VisualizationState = {
   threads: [ThreadInfo];
+  active_thread: number | null;
   heap_variables: [HeapVariable];
   current_source_file: [string, string, number];
+  request_failures: RequestFailure[];
+  build_output: string[];
+  breakpoints: Breakpoint[];
+  function_breakpoints: FunctionBreakpoint[];
+  data_breakpoints: DataBreakpoint[];
+  running: boolean;
+  exception: ExceptionInfo | null;
+  step_in_targets: StepInTarget[];
+  // requests waiting until the debug adapter is done with the current one
+  queued_user_requests: number;
+  exit_code: number | null;
+  // the debug session is over, this is the last state sent
+  terminated: boolean;
+  // how many times the program stopped so far
+  step: number;
+  console: ConsoleLine[];
 };
 
+// align these with the json tagged variants of `UserRequest`
+export type DapvizJsonRequest =
+  | {
+      request: "set_breakpoint" | "toggle_breakpoint";
+      file: string;
+      line: number;
+      condition?: string;
+      hit_condition?: string;
+      log_message?: string;
+    }
+  | { request: "clear_breakpoint"; file: string; line: number }
+  | { request: "set_function_breakpoint"; name: string; condition?: string }
+  | { request: "clear_function_breakpoint"; name: string }
+  // reference is the variables_reference of the scope or heap variable listing the variable
+  | { request: "set_data_breakpoint"; reference: number; name: string }
+  | { request: "clear_data_breakpoint"; data_id: string }
+  | { request: "run_to_line"; file: string; line: number; thread: number }
+  | { request: "step" | "step_out"; thread: number; granularity?: SteppingGranularity }
+  | {
+      request: "step_in";
+      thread: number;
+      // id of one of the step_in_targets
+      target?: number;
+      granularity?: SteppingGranularity;
+    }
+  | { request: "select_thread"; thread: number }
+  | { request: "restart_frame"; frame_id: number }
+  | { request: "restart_session" };
+
 type DapvizRequestFunction = (request: DapvizRequest, threadId: number) => void;
+type DapvizJsonRequestFunction = (request: DapvizJsonRequest) => void;
 
-const DapvizContext = createContext<[VisualizationState, DapvizRequestFunction] | null>(null);
+const DapvizContext = createContext<
+  [VisualizationState, DapvizRequestFunction, DapvizJsonRequestFunction] | null
+>(null);
 
 export const useDapviz = () => {
   const data = useContext(DapvizContext);
@@ -59,12 +165,18 @@ export const useDapviz = () => {
 const DapvizProvider = ({
   children,
   noConnection,
+  building,
 }: {
   children: ReactNode;
   noConnection: ReactElement;
+  building: (buildOutput: string[]) => ReactElement;
 }) => {
   const [requestFunction, setRequestFunction] = useState<DapvizRequestFunction | null>(null);
+  const [jsonRequestFunction, setJsonRequestFunction] = useState<DapvizJsonRequestFunction | null>(
+    null,
+  );
   const [visualizationState, setVisualizationState] = useState<VisualizationState | null>(null);
+  const [buildOutput, setBuildOutput] = useState<string[]>([]);
 
   useEffect(() => {
     const ws = new WebSocket(`ws://${location.host}/api/events`);
@@ -78,16 +190,26 @@ const DapvizProvider = ({
         return;
       }
 
-      if (json?.threads?.length > 0) {
+      if (json?.threads?.length > 0 || json?.terminated) {
         setVisualizationState(json);
       } else {
         setVisualizationState(null);
+        setBuildOutput(json?.build_output ?? []);
       }
     });
 
-    ws.addEventListener("close", () => {
+    ws.addEventListener("close", (e) => {
+      // the session ended normally, keep showing its final state without anything to request
+      if (e.code === 1000) {
+        setRequestFunction(() => () => {});
+        setJsonRequestFunction(() => () => {});
+        return;
+      }
+
       setRequestFunction(null);
+      setJsonRequestFunction(null);
       setVisualizationState(null);
+      setBuildOutput([]);
     });
 
     // NOTE: need to use the react state set function overload to set react state to a function
@@ -102,6 +224,9 @@ const DapvizProvider = ({
 
         ws.send(data);
       });
+      setJsonRequestFunction(
+        () => (request: DapvizJsonRequest) => ws.send(JSON.stringify(request)),
+      );
 
       // signal webserver that we're ready
       ws.send("ready");
@@ -110,10 +235,14 @@ const DapvizProvider = ({
     return () => ws.close();
   }, []);
 
-  return requestFunction !== null && visualizationState !== null ? (
-    <DapvizContext.Provider value={[visualizationState, requestFunction]}>
+  return requestFunction !== null &&
+    jsonRequestFunction !== null &&
+    visualizationState !== null ? (
+    <DapvizContext.Provider value={[visualizationState, requestFunction, jsonRequestFunction]}>
       {children}
     </DapvizContext.Provider>
+  ) : buildOutput.length > 0 ? (
+    building(buildOutput)
   ) : (
     noConnection
   );
@@ -12,7 +12,7 @@ import {
   useEdgesState,
   useNodesState,
 } from "@xyflow/react";
-import { HeapVariable, StackFrame, ThreadInfo, Variable } from "./DapvizProvider";
+import { HeapVariable, StackFrame, ThreadInfo, Variable, useDapviz } from "./DapvizProvider";
 
 import "@xyflow/react/dist/style.css";
 import { useTheme } from "./ThemeProvider";
@@ -110,6 +110,13 @@ export const SourceNodeComponent = (props: NodeProps<SourceNode>) => {
   const lines = sourceCode.split(/\r?\n/);
   const maxDigits = Math.ceil(Math.log10(lines.length + 1));
 
+  const [{ breakpoints }, , sendJson] = useDapviz();
+  const breakpointLines = new Set(
+    breakpoints
+      .filter((breakpoint) => breakpoint.file == fileName)
+      .map((breakpoint) => breakpoint.actual_line ?? breakpoint.line),
+  );
+
   return (
     <BaseNode>
       <BaseNodeHeader>
@@ -118,7 +125,17 @@ export const SourceNodeComponent = (props: NodeProps<SourceNode>) => {
       <div className="font-mono text-xs mt-4">
         {lines.map((line, i) => (
           <pre key={i} className={clsx(i + 1 == currentLine && "bg-yellow/30")}>
-            <span className="mr-2 text-neutral-500">{String(i + 1).padStart(maxDigits)}</span>{" "}
+            <span
+              className={clsx(
+                "mr-2 cursor-pointer",
+                breakpointLines.has(i + 1) ? "text-red-500" : "text-neutral-500",
+              )}
+              onClick={() =>
+                sendJson({ request: "toggle_breakpoint", file: fileName, line: i + 1 })
+              }
+            >
+              {String(i + 1).padStart(maxDigits)}
+            </span>{" "}
             {line}
           </pre>
         ))}